@@ -0,0 +1,95 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * February 11, 2023
+ */
+
+use bevy_math::{ivec3, IVec3};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Boundary {
+    Periodic,
+    Dead,
+    Alive,
+    Reflective
+}
+
+// what lies at a position once the boundary has been applied
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BoundaryCell {
+    Inside(IVec3),
+    Dead,
+    Alive
+}
+
+impl Boundary {
+    // mirror coordinate back into 0..size, repeating the reflection if needed
+    fn reflect(value: i32, size: i32) -> i32 {
+        let value = value.rem_euclid(2 * size);
+
+        if value >= size {
+            2 * size - 1 - value
+        } else {
+            value
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Boundaries {
+    pub x: Boundary,
+    pub y: Boundary,
+    pub z: Boundary
+}
+
+// implement Default trait for Boundaries, a 3-torus
+impl Default for Boundaries {
+    fn default() -> Self {
+        Boundaries::all(Boundary::Periodic)
+    }
+}
+
+impl Boundaries {
+    // use the same boundary on every axis
+    pub fn all(boundary: Boundary) -> Self {
+        Boundaries {
+            x: boundary,
+            y: boundary,
+            z: boundary
+        }
+    }
+
+    // apply boundaries to xyz position ; if a fixed boundary is crossed on several axes dead takes precedence over alive
+    pub fn resolve(&self, position: IVec3, bounds: IVec3) -> BoundaryCell {
+        let wrapped = utils::wrap(position, bounds);
+        let mut result = [0; 3];
+        let mut fixed = None;
+
+        for (axis, boundary) in [self.x, self.y, self.z].into_iter().enumerate() {
+            let value = position[axis];
+            let size = bounds[axis];
+
+            if (0..size).contains(&value) {
+                result[axis] = value;
+                continue;
+            }
+
+            match boundary {
+                Boundary::Periodic => result[axis] = wrapped[axis],
+                Boundary::Reflective => result[axis] = Boundary::reflect(value, size),
+                Boundary::Dead => fixed = Some(BoundaryCell::Dead),
+                Boundary::Alive => {
+                    if fixed.is_none() {
+                        fixed = Some(BoundaryCell::Alive);
+                    }
+                }
+            }
+        }
+
+        fixed.unwrap_or_else(|| BoundaryCell::Inside(ivec3(result[0], result[1], result[2])))
+    }
+}
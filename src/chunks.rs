@@ -0,0 +1,159 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * January 19, 2023
+ */
+
+use bevy_math::{ivec3, IVec3};
+
+use crate::utils;
+
+pub const CHUNK_SIZE: usize = 32;
+pub const CHUNK_CELL_COUNT: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+// convert index to chunk index
+pub fn index_to_chunk_index(index: usize) -> usize {
+    index / CHUNK_CELL_COUNT
+}
+
+// convert index to chunk offset
+pub fn index_to_chunk_offset(index: usize) -> usize {
+    index % CHUNK_CELL_COUNT
+}
+
+pub struct Chunk<Cell>(pub Vec<Cell>);
+
+// implement Default trait for Chunk
+impl<Cell: Default> Default for Chunk<Cell> {
+    fn default() -> Self {
+        let cells = (0..CHUNK_CELL_COUNT).map(|_| Cell::default()).collect::<Vec<_>>();
+
+        Chunk(cells)
+    }
+}
+
+impl<Cell> Chunk<Cell> {
+    // wrapper function to convert index to xyz position
+    pub fn index_to_position(index: usize) -> IVec3 {
+        utils::index_to_position(index, IVec3::splat(CHUNK_SIZE as i32))
+    }
+
+    // wrapper function to convert xyz position to index
+    pub fn position_to_index(position: IVec3) -> usize {
+        utils::position_to_index(position, IVec3::splat(CHUNK_SIZE as i32))
+    }
+
+    // returns true if xyz position lies within the used part of the chunk ; false otherwise
+    pub fn contains_position(position: IVec3, limit: IVec3) -> bool {
+        position.cmplt(limit).all()
+    }
+
+    // returns true if xyz position touches the border of the used part of the chunk ; false otherwise
+    pub fn is_border_position(position: IVec3, limit: IVec3) -> bool {
+        position.cmple(IVec3::ZERO).any() || position.cmpge(limit - 1).any()
+    }
+}
+
+pub struct Chunks<Cell> {
+    pub chunks: Vec<Chunk<Cell>>,
+    pub chunk_dimensions: IVec3,
+    pub chunk_count: usize,
+    bounds: IVec3
+}
+
+// implement Default trait for Chunks
+impl<Cell> Default for Chunks<Cell> {
+    fn default() -> Self {
+        Chunks::new()
+    }
+}
+
+impl<Cell> Chunks<Cell> {
+    // create new Chunks
+    pub fn new() -> Chunks<Cell> {
+        Chunks {
+            chunks: vec![],
+            chunk_dimensions: IVec3::ZERO,
+            chunk_count: 0,
+            bounds: IVec3::ZERO
+        }
+    }
+
+    // get bounds
+    pub fn bounds(&self) -> IVec3 {
+        self.bounds
+    }
+
+    // helper function to convert index to xyz position
+    pub fn index_to_position_ex(index: usize, chunk_dimensions: IVec3) -> IVec3 {
+        let chunk = index_to_chunk_index(index);
+        let offset = index_to_chunk_offset(index);
+        let chunk_vector = utils::index_to_position(chunk, chunk_dimensions);
+        let offset_vector = Chunk::<Cell>::index_to_position(offset);
+
+        (CHUNK_SIZE as i32 * chunk_vector) + offset_vector
+    }
+
+    // helper function to convert xyz position to index
+    pub fn position_to_index_ex(vector: IVec3, chunk_dimensions: IVec3) -> usize {
+        let chunk_vector = vector / CHUNK_SIZE as i32;
+        let offset_vector = vector % CHUNK_SIZE as i32;
+        let chunk = utils::position_to_index(chunk_vector, chunk_dimensions);
+        let offset = Chunk::<Cell>::position_to_index(offset_vector);
+
+        chunk * CHUNK_CELL_COUNT + offset
+    }
+
+    // convert index to xyz position
+    pub fn index_to_position(&self, index: usize) -> IVec3 {
+        Chunks::<Cell>::index_to_position_ex(index, self.chunk_dimensions)
+    }
+
+    // convert xyz position to index
+    pub fn position_to_index(&self, position: IVec3) -> usize {
+        Chunks::<Cell>::position_to_index_ex(position, self.chunk_dimensions)
+    }
+
+    // get size of the part of a chunk that lies within bounds ; chunks on the far side of the bounds may be partly unused
+    pub fn chunk_limit(&self, chunk_index: usize) -> IVec3 {
+        let origin = CHUNK_SIZE as i32 * utils::index_to_position(chunk_index, self.chunk_dimensions);
+
+        (self.bounds - origin).min(IVec3::splat(CHUNK_SIZE as i32))
+    }
+}
+
+impl<Cell: Copy> Chunks<Cell> {
+    // copy cells within bounds into a dense grid, x varying fastest, then y, then z
+    pub fn to_grid(&self) -> Vec<Cell> {
+        let mut grid = Vec::with_capacity((self.bounds.x * self.bounds.y * self.bounds.z) as usize);
+
+        for z in 0..self.bounds.z {
+            for y in 0..self.bounds.y {
+                for x in 0..self.bounds.x {
+                    let index = self.position_to_index(ivec3(x, y, z));
+
+                    grid.push(self.chunks[index_to_chunk_index(index)].0[index_to_chunk_offset(index)]);
+                }
+            }
+        }
+
+        grid
+    }
+}
+
+impl<Cell: Default> Chunks<Cell> {
+    // set bounds and update self ; chunks are cleared when the bounds change
+    pub fn set_bounds(&mut self, new_bounds: IVec3) -> IVec3 {
+        if new_bounds != self.bounds {
+            let dimensions = (new_bounds + CHUNK_SIZE as i32 - 1) / CHUNK_SIZE as i32;
+            let count = (dimensions.x * dimensions.y * dimensions.z) as usize;
+
+            self.chunks = (0..count).map(|_| Chunk::default()).collect();
+            self.chunk_dimensions = dimensions;
+            self.chunk_count = count;
+            self.bounds = new_bounds;
+        }
+
+        self.bounds()
+    }
+}
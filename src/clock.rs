@@ -0,0 +1,134 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * February 27, 2023
+ */
+
+const MIN_GENERATIONS_PER_SECOND: f32 = 0.25;
+const MAX_GENERATIONS_PER_SECOND: f32 = 960.0;
+
+// decides how many generations the active simulation advances each frame, independent of frame rate
+pub struct SimulationClock {
+    pub playing: bool,
+    pub generations_per_second: f32,
+    pub max_generations_per_frame: u32,
+    accumulated: f32,
+    // generations that were due but did not fit in the per frame limit, run in the next frames
+    carried: u32,
+    pending_steps: u32
+}
+
+// implement Default trait for SimulationClock
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self {
+            playing: false,
+            generations_per_second: 15.0,
+            max_generations_per_frame: 16,
+            accumulated: 0.0,
+            carried: 0,
+            pending_steps: 0
+        }
+    }
+}
+
+impl SimulationClock {
+    // toggle between playing and paused
+    pub fn toggle(&mut self) {
+        self.playing = !self.playing;
+        self.accumulated = 0.0;
+        self.carried = 0;
+    }
+
+    // advance by exactly one generation on the next tick, even while paused
+    pub fn step_once(&mut self) {
+        self.pending_steps += 1;
+    }
+
+    pub fn set_rate(&mut self, generations_per_second: f32) {
+        self.generations_per_second = generations_per_second.clamp(MIN_GENERATIONS_PER_SECOND, MAX_GENERATIONS_PER_SECOND);
+    }
+
+    // double the rate
+    pub fn faster(&mut self) {
+        self.set_rate(self.generations_per_second * 2.0);
+    }
+
+    // halve the rate
+    pub fn slower(&mut self) {
+        self.set_rate(self.generations_per_second / 2.0);
+    }
+
+    // get number of generations due after delta seconds ; generations beyond the per frame limit are carried over to the
+    // next frames, up to a second of them, so a short stall catches up but a rate the simulation cannot keep up with does
+    // not build an endless backlog
+    pub fn tick(&mut self, delta: f32) -> u32 {
+        let mut steps = std::mem::take(&mut self.pending_steps);
+
+        if !self.playing {
+            self.carried = 0;
+
+            return steps.min(self.max_generations_per_frame);
+        }
+
+        self.accumulated += delta * self.generations_per_second;
+
+        let due = self.accumulated.floor();
+
+        self.accumulated -= due;
+        steps += due as u32 + std::mem::take(&mut self.carried);
+
+        let frame_steps = steps.min(self.max_generations_per_frame);
+        let max_carried = (self.generations_per_second.ceil() as u32).max(self.max_generations_per_frame);
+
+        self.carried = (steps - frame_steps).min(max_carried);
+
+        frame_steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playing(generations_per_second: f32, max_generations_per_frame: u32) -> SimulationClock {
+        SimulationClock {
+            playing: true,
+            generations_per_second,
+            max_generations_per_frame,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn carries_generations_over_to_next_frames() {
+        let mut clock = playing(60.0, 4);
+
+        assert_eq!(clock.tick(0.1), 4);
+        assert_eq!(clock.tick(0.0), 2);
+        assert_eq!(clock.tick(0.0), 0);
+
+        // fractions of a generation add up over frames
+        assert_eq!((0..4).map(|_| clock.tick(0.01)).sum::<u32>(), 2);
+    }
+
+    #[test]
+    fn backlog_stays_bounded() {
+        let mut clock = playing(960.0, 16);
+
+        // a ten second stall catches up on at most one second of generations
+        assert_eq!(clock.tick(10.0), 16);
+        assert_eq!((0..100).map(|_| clock.tick(0.0)).sum::<u32>(), 960);
+
+        // pausing drops the backlog, stepping once still works
+        clock.tick(10.0);
+        clock.toggle();
+        clock.step_once();
+
+        assert_eq!(clock.tick(1.0), 1);
+
+        clock.toggle();
+
+        assert_eq!(clock.tick(0.0), 0);
+    }
+}
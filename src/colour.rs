@@ -0,0 +1,102 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * February 20, 2023
+ */
+
+use bevy_math::Vec4;
+
+use serde::{Deserialize, Serialize};
+
+// sRGB colour with alpha, written as 'Rgba(red: 1.0, green: 0.0, blue: 0.0, alpha: 1.0)' like Bevy's Color so rule
+// files stay the same
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "Rgba")]
+pub struct Colour {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+    pub alpha: f32
+}
+
+impl Colour {
+    pub const BLACK: Colour = Colour::rgb(0.0, 0.0, 0.0);
+    pub const BLUE: Colour = Colour::rgb(0.0, 0.0, 1.0);
+    pub const GOLD: Colour = Colour::rgb(1.0, 0.84, 0.0);
+    pub const GREEN: Colour = Colour::rgb(0.0, 1.0, 0.0);
+    pub const LIME_GREEN: Colour = Colour::rgb(0.2, 0.8, 0.2);
+    pub const ORANGE_RED: Colour = Colour::rgb(1.0, 0.27, 0.0);
+    pub const RED: Colour = Colour::rgb(1.0, 0.0, 0.0);
+
+    pub const fn rgb(red: f32, green: f32, blue: f32) -> Colour {
+        Colour::rgba(red, green, blue, 1.0)
+    }
+
+    pub const fn rgba(red: f32, green: f32, blue: f32, alpha: f32) -> Colour {
+        Colour {
+            red,
+            green,
+            blue,
+            alpha
+        }
+    }
+
+    pub fn as_rgba_f32(self) -> [f32; 4] {
+        [self.red, self.green, self.blue, self.alpha]
+    }
+
+    // get channels scaled to 0 - 255
+    pub fn as_rgba_u8(self) -> [u8; 4] {
+        self.as_rgba_f32().map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+}
+
+impl From<Colour> for Vec4 {
+    fn from(colour: Colour) -> Self {
+        Vec4::from(colour.as_rgba_f32())
+    }
+}
+
+impl From<Vec4> for Colour {
+    fn from(vec4: Vec4) -> Self {
+        Colour::rgba(vec4.x, vec4.y, vec4.z, vec4.w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rule::ColourMethod, utils};
+
+    #[test]
+    fn interpolates_channels_linearly() {
+        assert_eq!(utils::lerp_colour(Colour::RED, Colour::BLUE, 0.25), Colour::rgb(0.75, 0.0, 0.25));
+        assert_eq!(utils::lerp_colour(Colour::RED, Colour::BLUE, -1.0), Colour::RED);
+        assert_eq!(utils::lerp_colour(Colour::RED, Colour::BLUE, 2.0), Colour::BLUE);
+        assert_eq!(utils::lerp_colour(Colour::rgba(0.0, 0.0, 0.0, 0.0), Colour::GOLD, 0.5), Colour::rgba(0.5, 0.42, 0.0, 0.5));
+    }
+
+    #[test]
+    fn colours_cells_by_method() {
+        let method = ColourMethod::StateLerp(Colour::BLACK, Colour::RED);
+
+        assert_eq!(method.colour(4, 1, 0, 0.0), Colour::rgb(0.25, 0.0, 0.0));
+        assert_eq!(method.colour(4, 4, 0, 0.0), Colour::RED);
+
+        let method = ColourMethod::Neighbour(Colour::BLACK, Colour::GREEN);
+
+        assert_eq!(method.colour(4, 4, 13, 0.0), Colour::rgb(0.0, 0.5, 0.0));
+
+        let method = ColourMethod::DistanceToCenter(Colour::RED, Colour::BLUE);
+
+        assert_eq!(method.colour(4, 4, 0, 0.5), Colour::rgb(0.5, 0.0, 0.5));
+        assert_eq!(method.colour(4, 4, 0, 1.7), Colour::BLUE);
+        assert_eq!(ColourMethod::Single(Colour::GOLD).colour(4, 1, 26, 1.0), Colour::GOLD);
+    }
+
+    #[test]
+    fn scales_channels_to_bytes() {
+        assert_eq!(Colour::ORANGE_RED.as_rgba_u8(), [255, 69, 0, 255]);
+        assert_eq!(Colour::rgba(-0.5, 1.5, 0.5, 0.0).as_rgba_u8(), [0, 255, 128, 0]);
+    }
+}
@@ -0,0 +1,343 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * March 24, 2023
+ */
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt
+};
+
+use bevy_math::IVec3;
+
+use crate::{
+    engine::{Changes, Simulation},
+    rule::Rule
+};
+
+// repeated state : the run has been periodic since generation start
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Cycle {
+    pub start: u64,
+    // generations between repeats, 1 for a still life
+    pub period: u64
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.period == 1 {
+            write!(f, "still life since generation {}", self.start)
+        } else {
+            write!(f, "cycle of period {} since generation {}", self.period, self.start)
+        }
+    }
+}
+
+// SplitMix64 finaliser, spreads every input bit over the whole output
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+    value ^ (value >> 31)
+}
+
+// hash of one live cell ; the state hash is these xored together, so a cell changes it in constant time
+fn cell_hash(position: IVec3, value: u8) -> u64 {
+    let xy = position.x as u32 as u64 | (position.y as u32 as u64) << 32;
+    let z = (position.z as u32 as u64) << 8 | value as u64;
+
+    mix(mix(xy) ^ z)
+}
+
+// hash of the value of every cell, kept up to date from the births + deaths of every step
+pub struct StateHash {
+    hash: u64,
+    states: u8,
+    // cells below the full state, which lose a state every step without showing up in births + deaths
+    decaying: HashMap<IVec3, u8>
+}
+
+impl StateHash {
+    pub fn from_simulation(simulation: &dyn Simulation, rule: &Rule) -> StateHash {
+        let mut hash = 0;
+        let mut decaying = HashMap::new();
+
+        for (position, cell) in simulation.cells() {
+            hash ^= cell_hash(position, cell.value);
+
+            if cell.value < rule.states {
+                decaying.insert(position, cell.value);
+            }
+        }
+
+        StateHash {
+            hash,
+            states: rule.states,
+            decaying
+        }
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    // apply one step in the same order as the engines : decaying cells lose a state, then births enter the first
+    // state and deaths leave it
+    pub fn apply(&mut self, changes: &Changes) {
+        let hash = &mut self.hash;
+
+        self.decaying.retain(|position, value| {
+            *hash ^= cell_hash(*position, *value);
+            *value -= 1;
+
+            if *value > 0 {
+                *hash ^= cell_hash(*position, *value);
+            }
+
+            *value > 0
+        });
+
+        for position in &changes.births {
+            if let Some(value) = self.decaying.remove(position) {
+                self.hash ^= cell_hash(*position, value);
+            }
+
+            self.hash ^= cell_hash(*position, self.states);
+        }
+
+        for position in &changes.deaths {
+            self.hash ^= cell_hash(*position, self.states);
+
+            if self.states > 1 {
+                self.hash ^= cell_hash(*position, self.states - 1);
+                self.decaying.insert(*position, self.states - 1);
+            }
+        }
+    }
+}
+
+// remembers the state hash of recent generations and reports the first one that repeats ; states are compared by
+// hash only, and a repeat only means a cycle when the rule does not seed noise along the way
+pub struct CycleDetector {
+    state: Option<StateHash>,
+    // generation each hash was first seen at, oldest first in order
+    seen: HashMap<u64, u64>,
+    order: VecDeque<u64>,
+    capacity: usize,
+    cycle: Option<Cycle>
+}
+
+impl CycleDetector {
+    // create new CycleDetector remembering at most capacity generations, which bounds the longest period found
+    pub fn new(capacity: usize) -> CycleDetector {
+        CycleDetector {
+            state: None,
+            seen: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+            cycle: None
+        }
+    }
+
+    // forget every generation and the cycle found, e.g. once the cells were replaced
+    pub fn clear(&mut self) {
+        self.state = None;
+        self.seen.clear();
+        self.order.clear();
+        self.cycle = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.is_none()
+    }
+
+    pub fn hash(&self) -> Option<u64> {
+        self.state.as_ref().map(StateHash::hash)
+    }
+
+    // get cycle found since the last clear
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.cycle
+    }
+
+    // forget every generation, then hash the current generation of a simulation as the first one
+    pub fn start(&mut self, simulation: &dyn Simulation, rule: &Rule) {
+        self.clear();
+
+        let state = StateHash::from_simulation(simulation, rule);
+
+        self.remember(simulation.generation(), state.hash());
+        self.state = Some(state);
+    }
+
+    // update hash with the births + deaths that led to generation ; returns the cycle when this is the first repeat
+    pub fn push(&mut self, generation: u64, changes: &Changes) -> Option<Cycle> {
+        let hash = match &mut self.state {
+            Some(state) => {
+                state.apply(changes);
+                state.hash()
+            },
+            None => return None
+        };
+
+        match self.seen.get(&hash) {
+            Some(start) if self.cycle.is_none() => {
+                let cycle = Cycle {
+                    start: *start,
+                    period: generation - start
+                };
+
+                self.cycle = Some(cycle);
+
+                Some(cycle)
+            },
+            Some(_) => None,
+            None => {
+                self.remember(generation, hash);
+
+                None
+            }
+        }
+    }
+
+    fn remember(&mut self, generation: u64, hash: u64) {
+        self.seen.insert(hash, generation);
+        self.order.push_back(hash);
+
+        while self.order.len() > self.capacity {
+            if let Some(hash) = self.order.pop_front() {
+                self.seen.remove(&hash);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bevy_math::ivec3;
+
+    use super::*;
+    use crate::multi_threading::MultiThreaded;
+
+    const A: IVec3 = IVec3::ZERO;
+    const B: IVec3 = IVec3::X;
+    const C: IVec3 = IVec3::Y;
+
+    fn rule(states: u8) -> Rule {
+        Rule {
+            states,
+            bounding_size: IVec3::splat(16),
+            ..Default::default()
+        }
+    }
+
+    fn simulation(rule: &Rule, positions: &[IVec3]) -> MultiThreaded {
+        let mut simulation = MultiThreaded::new();
+
+        simulation.set_bounds(rule.bounding_size);
+        simulation.seed(rule, positions);
+        simulation
+    }
+
+    // births + deaths moving a single live cell from one position to another
+    fn moving(from: IVec3, to: IVec3) -> Changes {
+        Changes {
+            births: vec![to],
+            deaths: vec![from]
+        }
+    }
+
+    #[test]
+    fn finds_period_and_start_of_blinker() {
+        let rule = rule(1);
+        let mut cycles = CycleDetector::new(16);
+
+        cycles.start(&simulation(&rule, &[A]), &rule);
+
+        assert_eq!(cycles.push(1, &moving(A, B)), None);
+        assert_eq!(cycles.push(2, &moving(B, C)), None);
+        assert_eq!(cycles.push(3, &moving(C, B)), Some(Cycle {start: 1, period: 2}));
+        assert_eq!(cycles.push(4, &moving(B, C)), None);
+        assert_eq!(cycles.cycle(), Some(Cycle {start: 1, period: 2}));
+        assert_eq!(cycles.cycle().unwrap().to_string(), "cycle of period 2 since generation 1");
+    }
+
+    #[test]
+    fn decaying_cells_change_the_hash() {
+        let rule = rule(3);
+        let mut cycles = CycleDetector::new(16);
+
+        cycles.start(&simulation(&rule, &[A]), &rule);
+
+        let mut hashes = vec![cycles.hash().unwrap()];
+        let death = Changes {
+            births: vec![],
+            deaths: vec![A]
+        };
+
+        // the cell dies through states 2 and 1 without showing up in the changes again
+        assert_eq!(cycles.push(1, &death), None);
+        hashes.push(cycles.hash().unwrap());
+        assert_eq!(cycles.push(2, &Changes::default()), None);
+        hashes.push(cycles.hash().unwrap());
+        assert_eq!(cycles.push(3, &Changes::default()), None);
+        hashes.push(cycles.hash().unwrap());
+
+        assert_eq!(hashes.iter().collect::<HashSet<_>>().len(), 4);
+        assert_eq!(hashes[3], 0);
+        assert_eq!(cycles.push(4, &Changes::default()), Some(Cycle {start: 3, period: 1}));
+    }
+
+    #[test]
+    fn hash_follows_the_engine() {
+        let rule = "4-6/4/4/M".parse::<Rule>().map(|rule| Rule {
+            bounding_size: IVec3::splat(16),
+            ..rule
+        }).unwrap();
+        let positions: Vec<IVec3> = (0..64).map(|index| ivec3(index % 4, index / 4 % 4, index / 16) + IVec3::splat(6)).collect();
+        let mut simulation = simulation(&rule, &positions);
+        let mut cycles = CycleDetector::new(64);
+
+        simulation.record_changes(true);
+        simulation.take_changes();
+        cycles.start(&simulation, &rule);
+
+        let mut decayed = false;
+
+        for _ in 0..12 {
+            simulation.step(&rule);
+            cycles.push(simulation.generation(), &simulation.take_changes());
+            decayed |= simulation.cells().any(|(_, cell)| cell.value < rule.states);
+
+            assert_eq!(cycles.hash(), Some(StateHash::from_simulation(&simulation, &rule).hash()));
+        }
+
+        assert!(decayed);
+    }
+
+    #[test]
+    fn evicted_generations_give_no_false_cycle() {
+        let rule = rule(1);
+        let steps = [moving(A, B), moving(B, C), moving(C, A)];
+
+        // a period 3 cycle cannot be found by remembering 2 generations
+        let mut cycles = CycleDetector::new(2);
+
+        cycles.start(&simulation(&rule, &[A]), &rule);
+
+        for generation in 1..=12 {
+            assert_eq!(cycles.push(generation, &steps[(generation as usize - 1) % 3]), None);
+        }
+
+        let mut cycles = CycleDetector::new(3);
+
+        cycles.start(&simulation(&rule, &[A]), &rule);
+
+        assert_eq!(cycles.push(1, &steps[0]), None);
+        assert_eq!(cycles.push(2, &steps[1]), None);
+        assert_eq!(cycles.push(3, &steps[2]), Some(Cycle {start: 0, period: 3}));
+    }
+}
@@ -0,0 +1,108 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * February 24, 2023
+ */
+
+use bevy_math::IVec3;
+
+use crate::{
+    colour::Colour,
+    multi_threading::Cell,
+    rule::{Rule, RuleError},
+    snapshot::Snapshot,
+    utils
+};
+
+// cells that came alive and cells that started dying, as xyz positions
+#[derive(Clone, Default)]
+pub struct Changes {
+    pub births: Vec<IVec3>,
+    pub deaths: Vec<IVec3>
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.births.is_empty() && self.deaths.is_empty()
+    }
+}
+
+// interface shared by every simulation engine so it can be driven by input, scripts or tests alike
+pub trait Simulation: Send + Sync {
+    // advance simulation by one generation
+    fn step(&mut self, rule: &Rule);
+    // set cells at xyz positions alive
+    fn seed(&mut self, rule: &Rule, positions: &[IVec3]);
+    // kill every cell, keeping bounds
+    fn clear(&mut self);
+    // replace every cell with the given xyz positions and values and set the generation, recomputing neighbour counts
+    fn restore(&mut self, rule: &Rule, cells: &[(IVec3, u8)], generation: u64);
+    // set the values of cells at xyz positions in place and set the generation, keeping every other cell ; neighbour
+    // counts follow the cells entering or leaving their first state
+    fn set_cells(&mut self, rule: &Rule, cells: &[(IVec3, u8)], generation: u64);
+    // start or stop collecting births + deaths of seeds and steps ; nothing is collected while stopped
+    fn record_changes(&mut self, enabled: bool);
+    // take births + deaths collected since the last call
+    fn take_changes(&mut self) -> Changes;
+    // iterate over xyz position and state of every live cell
+    fn cells(&self) -> Box<dyn Iterator<Item = (IVec3, Cell)> + '_>;
+    fn cell_count(&self) -> usize;
+    // get number of generations stepped since the simulation was cleared
+    fn generation(&self) -> u64;
+    fn set_bounds(&mut self, new_bounds: IVec3) -> IVec3;
+    fn bounds(&self) -> IVec3;
+    // get xyz position the world is centered on
+    fn center(&self) -> IVec3;
+
+    // check the simulation can run rule, which has been validated on its own
+    fn check_rule(&self, _rule: &Rule) -> Result<(), RuleError> {
+        Ok(())
+    }
+
+    // get every cell within bounds around the center as a dense grid, x varying fastest, then y, then z
+    fn grid(&self) -> Vec<Cell> {
+        let bounds = self.bounds();
+        let origin = self.center() - utils::center(bounds);
+        let mut grid = vec![Cell::default(); (bounds.x * bounds.y * bounds.z).max(0) as usize];
+
+        for (position, cell) in self.cells() {
+            let position = position - origin;
+
+            if position.cmpge(IVec3::ZERO).all() && position.cmplt(bounds).all() {
+                grid[utils::position_to_index(position, bounds)] = cell;
+            }
+        }
+
+        grid
+    }
+
+    // get colour of the cell at xyz position using the rule's colour method
+    fn colour(&self, rule: &Rule, position: IVec3, cell: Cell) -> Colour {
+        let bounds = self.bounds();
+        let distance_to_center = utils::distance_to_center(position - self.center() + utils::center(bounds), bounds);
+
+        rule.colour_method.colour(rule.states, cell.value, cell.neighbours, distance_to_center)
+    }
+
+    // capture the full state of the simulation, together with the rule and random number generator it runs with
+    fn snapshot(&self, rule: &Rule, seed: u64, rng_position: u128) -> Snapshot {
+        Snapshot {
+            rule: rule.clone(),
+            bounds: self.bounds(),
+            generation: self.generation(),
+            seed,
+            rng_position,
+            cells: self.cells().map(|(position, cell)| (position - self.center(), cell.value)).collect()
+        }
+    }
+
+    // replace the state of the simulation with the snapshot's
+    fn load_snapshot(&mut self, snapshot: &Snapshot) {
+        self.set_bounds(snapshot.bounds);
+
+        let center = self.center();
+        let cells: Vec<(IVec3, u8)> = snapshot.cells.iter().map(|(position, value)| (*position + center, *value)).collect();
+
+        self.restore(&snapshot.rule, &cells, snapshot.generation);
+    }
+}
@@ -0,0 +1,428 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * March 6, 2023
+ */
+
+use std::fmt;
+
+use bevy_math::{ivec3, IVec3};
+
+use rand::Rng;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    seeding::SeedRng,
+    utils
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Axis {
+    X,
+    Y,
+    Z
+}
+
+impl Axis {
+    // get unit vector along axis
+    fn unit(self) -> IVec3 {
+        match self {
+            Axis::X => IVec3::X,
+            Axis::Y => IVec3::Y,
+            Axis::Z => IVec3::Z
+        }
+    }
+}
+
+// symmetry enforced on a soup, about the center it is generated around
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Symmetry {
+    Mirror {x: bool, y: bool, z: bool},
+    Rotate(Axis),
+    Octahedral
+}
+
+impl Symmetry {
+    // get every offset the symmetry maps offset to, including offset itself
+    pub fn images(&self, offset: IVec3) -> Vec<IVec3> {
+        match *self {
+            Symmetry::Mirror {x, y, z} => {
+                let mut images = vec![offset];
+
+                for (mirrored, flip) in [(x, ivec3(-1, 1, 1)), (y, ivec3(1, -1, 1)), (z, ivec3(1, 1, -1))] {
+                    if mirrored {
+                        let flipped: Vec<IVec3> = images.iter().map(|image| *image * flip).collect();
+
+                        images.extend(flipped);
+                    }
+                }
+
+                images
+            },
+            Symmetry::Rotate(axis) => {
+                let mut images = vec![offset];
+
+                for _ in 0..3 {
+                    let last = images[images.len() - 1];
+
+                    images.push(match axis {
+                        Axis::X => ivec3(last.x, -last.z, last.y),
+                        Axis::Y => ivec3(last.z, last.y, -last.x),
+                        Axis::Z => ivec3(-last.y, last.x, last.z)
+                    });
+                }
+
+                images
+            },
+            Symmetry::Octahedral => {
+                let [x, y, z] = offset.to_array();
+                let mut images = Vec::with_capacity(48);
+
+                for permutation in [[x, y, z], [x, z, y], [y, x, z], [y, z, x], [z, x, y], [z, y, x]] {
+                    for signs in 0..8 {
+                        images.push(ivec3(
+                            if signs & 1 == 0 { permutation[0] } else { -permutation[0] },
+                            if signs & 2 == 0 { permutation[1] } else { -permutation[1] },
+                            if signs & 4 == 0 { permutation[2] } else { -permutation[2] }
+                        ));
+                    }
+                }
+
+                images
+            }
+        }
+    }
+
+    // get name of symmetry
+    pub fn name(&self) -> &'static str {
+        match self {
+            Symmetry::Mirror {x: true, y: true, z: true} => "mirrored soup (3 axes)",
+            Symmetry::Mirror {x, y, z} if (*x as u8 + *y as u8 + *z as u8) == 2 => "mirrored soup (2 axes)",
+            Symmetry::Mirror {..} => "mirrored soup",
+            Symmetry::Rotate(_) => "rotational soup",
+            Symmetry::Octahedral => "octahedral soup"
+        }
+    }
+}
+
+// largest radius a generator may cover, bounding the (2 * radius + 1)^3 offsets it visits
+const MAX_RADIUS: i32 = 256;
+// most cells a generator may place at random
+const MAX_AMOUNT: usize = 1 << 24;
+
+// initial conditions ; density is the chance each cell of the shape is alive
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Generator {
+    Noise {radius: i32, amount: usize},
+    Sphere {radius: i32, density: f32},
+    Shell {radius: i32, thickness: i32, density: f32},
+    Cube {radius: i32, density: f32},
+    SingleCell,
+    Plane {normal: Axis, radius: i32, density: f32},
+    Line {axis: Axis, radius: i32, density: f32},
+    Gaussian {sigma: f32, amount: usize},
+    Fill {density: f32},
+    Soup {radius: i32, density: f32, symmetry: Symmetry}
+}
+
+// implement Default trait for Generator, a 13 x 13 x 13 cube of noise
+impl Default for Generator {
+    fn default() -> Self {
+        Generator::Noise {radius: 6, amount: 12 * 12 * 12}
+    }
+}
+
+impl Generator {
+    // get one generator of each kind with its default size and density
+    pub fn all() -> [Generator; 14] {
+        [
+            Generator::default(),
+            Generator::Sphere {radius: 8, density: 0.5},
+            Generator::Shell {radius: 10, thickness: 2, density: 0.5},
+            Generator::Cube {radius: 6, density: 0.5},
+            Generator::SingleCell,
+            Generator::Plane {normal: Axis::Y, radius: 12, density: 0.5},
+            Generator::Line {axis: Axis::X, radius: 12, density: 1.0},
+            Generator::Gaussian {sigma: 4.0, amount: 12 * 12 * 12},
+            Generator::Fill {density: 0.1},
+            Generator::Soup {radius: 8, density: 0.3, symmetry: Symmetry::Mirror {x: true, y: false, z: false}},
+            Generator::Soup {radius: 8, density: 0.3, symmetry: Symmetry::Mirror {x: true, y: true, z: false}},
+            Generator::Soup {radius: 8, density: 0.3, symmetry: Symmetry::Mirror {x: true, y: true, z: true}},
+            Generator::Soup {radius: 8, density: 0.3, symmetry: Symmetry::Rotate(Axis::Y)},
+            Generator::Soup {radius: 8, density: 0.3, symmetry: Symmetry::Octahedral}
+        ]
+    }
+
+    // get name of generator kind
+    pub fn name(&self) -> &'static str {
+        match self {
+            Generator::Noise {..} => "noise",
+            Generator::Sphere {..} => "sphere",
+            Generator::Shell {..} => "shell",
+            Generator::Cube {..} => "cube",
+            Generator::SingleCell => "single cell",
+            Generator::Plane {..} => "plane",
+            Generator::Line {..} => "line",
+            Generator::Gaussian {..} => "gaussian",
+            Generator::Fill {..} => "fill",
+            Generator::Soup {symmetry, ..} => symmetry.name()
+        }
+    }
+
+    // check sizes are within range and densities are chances, so positions can always be generated
+    pub fn validate(&self) -> Result<(), GeneratorError> {
+        let (radius, density, amount) = match *self {
+            Generator::Noise {radius, amount} => (Some(radius), None, Some(amount)),
+            Generator::Sphere {radius, density} | Generator::Cube {radius, density} => (Some(radius), Some(density), None),
+            Generator::Shell {radius, thickness, density} => {
+                if !(0..=radius).contains(&thickness) {
+                    return Err(GeneratorError::InvalidThickness(thickness));
+                }
+
+                (Some(radius), Some(density), None)
+            },
+            Generator::SingleCell => (None, None, None),
+            Generator::Plane {radius, density, ..} | Generator::Line {radius, density, ..} => (Some(radius), Some(density), None),
+            Generator::Gaussian {sigma, amount} => {
+                if !(sigma.is_finite() && sigma >= 0.0) {
+                    return Err(GeneratorError::InvalidSigma(sigma));
+                }
+
+                (None, None, Some(amount))
+            },
+            Generator::Fill {density} => (None, Some(density), None),
+            Generator::Soup {radius, density, ..} => (Some(radius), Some(density), None)
+        };
+
+        if let Some(radius) = radius.filter(|radius| !(0..=MAX_RADIUS).contains(radius)) {
+            return Err(GeneratorError::InvalidRadius(radius));
+        }
+
+        if let Some(density) = density.filter(|density| !(0.0..=1.0).contains(density)) {
+            return Err(GeneratorError::InvalidDensity(density));
+        }
+
+        if let Some(amount) = amount.filter(|amount| *amount > MAX_AMOUNT) {
+            return Err(GeneratorError::InvalidAmount(amount));
+        }
+
+        Ok(())
+    }
+
+    // get positions of live cells around center ; bounds are the size of the world, filled by Fill
+    pub fn positions(&self, center: IVec3, bounds: IVec3, rng: &mut SeedRng) -> Vec<IVec3> {
+        let mut positions = vec![];
+
+        match *self {
+            Generator::Noise {radius, amount} => {
+                utils::spawn_noise(center, radius, amount, rng, |position| positions.push(position));
+            },
+            Generator::Sphere {radius, density} => {
+                for offset in cube(radius) {
+                    if offset.dot(offset) <= radius * radius && alive(rng, density) {
+                        positions.push(center + offset);
+                    }
+                }
+            },
+            Generator::Shell {radius, thickness, density} => {
+                let inner = (radius - thickness).max(0);
+
+                for offset in cube(radius) {
+                    let distance = offset.dot(offset);
+
+                    if distance <= radius * radius && distance > inner * inner && alive(rng, density) {
+                        positions.push(center + offset);
+                    }
+                }
+            },
+            Generator::Cube {radius, density} => {
+                for offset in cube(radius) {
+                    if alive(rng, density) {
+                        positions.push(center + offset);
+                    }
+                }
+            },
+            Generator::SingleCell => positions.push(center),
+            Generator::Plane {normal, radius, density} => {
+                for offset in cube(radius) {
+                    if offset.dot(normal.unit()) == 0 && alive(rng, density) {
+                        positions.push(center + offset);
+                    }
+                }
+            },
+            Generator::Line {axis, radius, density} => {
+                for step in -radius..=radius {
+                    if alive(rng, density) {
+                        positions.push(center + axis.unit() * step);
+                    }
+                }
+            },
+            Generator::Gaussian {sigma, amount} => {
+                for _ in 0..amount {
+                    let offset = ivec3(gaussian(rng, sigma), gaussian(rng, sigma), gaussian(rng, sigma));
+
+                    positions.push(center + offset);
+                }
+            },
+            Generator::Fill {density} => {
+                let origin = center - utils::center(bounds);
+
+                for z in 0..bounds.z {
+                    for y in 0..bounds.y {
+                        for x in 0..bounds.x {
+                            if alive(rng, density) {
+                                positions.push(origin + ivec3(x, y, z));
+                            }
+                        }
+                    }
+                }
+            },
+            Generator::Soup {radius, density, symmetry} => {
+                // only roll for one offset of each set of images so the soup is exactly symmetric
+                for offset in cube(radius) {
+                    let mut images = symmetry.images(offset);
+
+                    images.sort_by_key(|image| image.to_array());
+                    images.dedup();
+
+                    if images[0] == offset && alive(rng, density) {
+                        positions.extend(images.into_iter().map(|image| center + image));
+                    }
+                }
+            }
+        }
+
+        positions
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GeneratorError {
+    InvalidRadius(i32),
+    InvalidThickness(i32),
+    InvalidDensity(f32),
+    InvalidSigma(f32),
+    InvalidAmount(usize)
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GeneratorError::InvalidRadius(radius) => write!(f, "generator radius {} is outside the range 0-{}", radius, MAX_RADIUS),
+            GeneratorError::InvalidThickness(thickness) => {
+                write!(f, "generator thickness {} must be between 0 and the radius", thickness)
+            },
+            GeneratorError::InvalidDensity(density) => write!(f, "generator density {} is outside the range 0-1", density),
+            GeneratorError::InvalidSigma(sigma) => write!(f, "generator sigma {} must be a number of at least 0", sigma),
+            GeneratorError::InvalidAmount(amount) => write!(f, "generator amount {} is above the limit of {}", amount, MAX_AMOUNT)
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+// iterate over every offset within radius on each axis
+fn cube(radius: i32) -> impl Iterator<Item = IVec3> {
+    (-radius..=radius).flat_map(move |z| {
+        (-radius..=radius).flat_map(move |y| (-radius..=radius).map(move |x| ivec3(x, y, z)))
+    })
+}
+
+// returns true with chance density
+fn alive(rng: &mut SeedRng, density: f32) -> bool {
+    rng.gen_bool(density.clamp(0.0, 1.0) as f64)
+}
+
+// sample normal distribution with mean 0 using the Box-Muller transform, rounded to the nearest cell
+fn gaussian(rng: &mut SeedRng, sigma: f32) -> i32 {
+    let u1 = 1.0 - rng.gen::<f32>();
+    let u2 = rng.gen::<f32>();
+
+    ((-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos() * sigma).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_positions() {
+        let center = ivec3(25, 25, 25);
+        let bounds = ivec3(50, 50, 50);
+
+        for generator in Generator::all() {
+            let positions = generator.positions(center, bounds, &mut SeedRng::seed_from_u64(7));
+
+            assert_eq!(positions, generator.positions(center, bounds, &mut SeedRng::seed_from_u64(7)), "{}", generator.name());
+            assert!(!positions.is_empty(), "{}", generator.name());
+        }
+
+        let noise = Generator::default();
+
+        assert_ne!(
+            noise.positions(center, bounds, &mut SeedRng::seed_from_u64(7)),
+            noise.positions(center, bounds, &mut SeedRng::seed_from_u64(8))
+        );
+    }
+
+    #[test]
+    fn positions_stay_inside_bounds() {
+        let bounds = ivec3(50, 50, 50);
+        let center = utils::center(bounds);
+
+        for generator in Generator::all() {
+            for position in generator.positions(center, bounds, &mut SeedRng::seed_from_u64(11)) {
+                assert!(position.cmpge(IVec3::ZERO).all() && position.cmplt(bounds).all(), "{} placed {}", generator.name(), position);
+            }
+        }
+    }
+
+    #[test]
+    fn soups_contain_every_image() {
+        let center = ivec3(25, 25, 25);
+
+        for generator in Generator::all() {
+            let Generator::Soup {symmetry, ..} = generator else {
+                continue;
+            };
+
+            let positions = generator.positions(center, ivec3(50, 50, 50), &mut SeedRng::seed_from_u64(3));
+            let set: HashSet<IVec3> = positions.iter().copied().collect();
+
+            assert!(!set.is_empty());
+            assert_eq!(set.len(), positions.len(), "{} placed a cell twice", symmetry.name());
+
+            for position in &set {
+                for image in symmetry.images(*position - center) {
+                    assert!(set.contains(&(center + image)), "{} is missing {}", symmetry.name(), center + image);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn images_match_symmetry_order() {
+        let offset = ivec3(1, 2, 3);
+        let count = |symmetry: Symmetry| symmetry.images(offset).into_iter().collect::<HashSet<IVec3>>().len();
+
+        assert_eq!(count(Symmetry::Mirror {x: true, y: false, z: false}), 2);
+        assert_eq!(count(Symmetry::Mirror {x: true, y: true, z: false}), 4);
+        assert_eq!(count(Symmetry::Mirror {x: true, y: true, z: true}), 8);
+        assert_eq!(count(Symmetry::Rotate(Axis::Y)), 4);
+        assert_eq!(count(Symmetry::Octahedral), 48);
+        assert_eq!(Symmetry::Rotate(Axis::Z).images(offset)[1], ivec3(-2, 1, 3));
+    }
+
+    #[test]
+    fn names_every_generator() {
+        let names: HashSet<&str> = Generator::all().iter().map(Generator::name).collect();
+
+        assert_eq!(names.len(), Generator::all().len());
+        assert!(names.contains("mirrored soup (2 axes)"));
+    }
+}
@@ -0,0 +1,334 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * March 20, 2023
+ */
+
+use std::{
+    collections::{HashMap, VecDeque},
+    mem
+};
+
+use bevy_math::IVec3;
+
+use crate::{
+    engine::{Changes, Simulation},
+    recording::apply_changes,
+    rule::Rule
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HistoryMode {
+    // every generation is kept as a full state, fast to rewind but large
+    Full,
+    // a full state every interval generations with the births + deaths in between, small but rewinding replays them
+    Delta(u32)
+}
+
+// generation with the random number generator position reached after it, so stepping on gives the same noise
+struct Keyframe {
+    generation: u64,
+    rng_position: u128,
+    cells: Vec<(IVec3, u8)>,
+    // births + deaths of each following generation and the random number generator position after it
+    deltas: Vec<(Changes, u128)>
+}
+
+// approximate memory used by the births + deaths of a generation
+fn delta_bytes(changes: &Changes) -> usize {
+    (changes.births.len() + changes.deaths.len()) * mem::size_of::<IVec3>() + mem::size_of::<(Changes, u128)>()
+}
+
+impl Keyframe {
+    // approximate memory used, including deltas
+    fn bytes(&self) -> usize {
+        let cells = self.cells.len() * mem::size_of::<(IVec3, u8)>();
+        let deltas = self.deltas.iter().map(|(changes, _)| delta_bytes(changes)).sum::<usize>();
+
+        mem::size_of::<Keyframe>() + cells + deltas
+    }
+}
+
+// ring buffer of past generations of a simulation, dropping the oldest once it outgrows its memory budget
+pub struct History {
+    mode: HistoryMode,
+    budget: usize,
+    keyframes: VecDeque<Keyframe>,
+    bytes: usize
+}
+
+impl History {
+    // create new History using at most about budget bytes
+    pub fn new(mode: HistoryMode, budget: usize) -> History {
+        History {
+            mode,
+            budget,
+            keyframes: VecDeque::new(),
+            bytes: 0
+        }
+    }
+
+    pub fn mode(&self) -> HistoryMode {
+        self.mode
+    }
+
+    // switch mode, forgetting every stored generation
+    pub fn set_mode(&mut self, mode: HistoryMode) {
+        self.mode = mode;
+        self.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+        self.bytes = 0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    // get oldest generation that can be rewound to
+    pub fn oldest(&self) -> Option<u64> {
+        self.keyframes.front().map(|keyframe| keyframe.generation)
+    }
+
+    // get newest generation stored
+    pub fn newest(&self) -> Option<u64> {
+        self.keyframes.back().map(|keyframe| keyframe.generation + keyframe.deltas.len() as u64)
+    }
+
+    // get approximate memory used
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    // store the current generation of a simulation ; changes are the births + deaths that led to it from the newest
+    // generation stored, without them a full state is kept
+    pub fn push(&mut self, simulation: &dyn Simulation, changes: Option<Changes>, rng_position: u128) {
+        let interval = match self.mode {
+            HistoryMode::Full => 1,
+            HistoryMode::Delta(interval) => interval.max(1) as usize
+        };
+        let follows = self.newest().is_some_and(|newest| newest + 1 == simulation.generation());
+        let extends = follows && self.keyframes.back().is_some_and(|keyframe| keyframe.deltas.len() + 1 < interval);
+
+        match (changes, extends) {
+            (Some(changes), true) => {
+                self.bytes += delta_bytes(&changes);
+
+                if let Some(keyframe) = self.keyframes.back_mut() {
+                    keyframe.deltas.push((changes, rng_position));
+                }
+            },
+            _ => {
+                let keyframe = Keyframe {
+                    generation: simulation.generation(),
+                    rng_position,
+                    cells: simulation.cells().map(|(position, cell)| (position, cell.value)).collect(),
+                    deltas: vec![]
+                };
+
+                // a keyframe that does not follow on from the newest generation starts a new timeline
+                if !follows {
+                    self.clear();
+                }
+
+                self.bytes += keyframe.bytes();
+                self.keyframes.push_back(keyframe);
+            }
+        }
+
+        // drop the oldest keyframes with their deltas, always keeping the newest
+        while self.bytes > self.budget && self.keyframes.len() > 1 {
+            if let Some(keyframe) = self.keyframes.pop_front() {
+                self.bytes -= keyframe.bytes();
+            }
+        }
+    }
+
+    // go back generations, clamped to the oldest stored, restoring that generation into the simulation and forgetting
+    // every later one so stepping on resumes from there ; returns the random number generator position to resume with,
+    // or None without touching the simulation when there is no older generation
+    pub fn rewind(&mut self, generations: u64, simulation: &mut dyn Simulation, rule: &Rule) -> Option<u128> {
+        let newest = self.newest()?;
+        let target = newest.saturating_sub(generations).max(self.oldest()?);
+
+        if target == newest {
+            return None;
+        }
+
+        // forget keyframes after the target
+        while self.keyframes.back().is_some_and(|keyframe| keyframe.generation > target) {
+            if let Some(keyframe) = self.keyframes.pop_back() {
+                self.bytes -= keyframe.bytes();
+            }
+        }
+
+        let keyframe = self.keyframes.back_mut()?;
+
+        for (changes, _) in keyframe.deltas.drain((target - keyframe.generation) as usize..) {
+            self.bytes -= delta_bytes(&changes);
+        }
+
+        let mut cells: HashMap<IVec3, u8> = keyframe.cells.iter().copied().collect();
+
+        for (changes, _) in &keyframe.deltas {
+            apply_changes(&mut cells, changes, rule.states);
+        }
+
+        let rng_position = keyframe.deltas.last().map_or(keyframe.rng_position, |(_, rng_position)| *rng_position);
+
+        simulation.restore(rule, &cells.into_iter().collect::<Vec<_>>(), target);
+
+        Some(rng_position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::{
+        multi_threading::MultiThreaded,
+        seeding::{SeedPolicy, SeedRng}
+    };
+
+    const GENERATIONS: usize = 40;
+
+    fn rule() -> Rule {
+        let mut rule = "9-26/5-7,12-13,15/5/M".parse::<Rule>().unwrap();
+
+        rule.bounding_size = IVec3::splat(32);
+        rule.seeding.policy = SeedPolicy::Every(8);
+
+        rule
+    }
+
+    fn state(simulation: &dyn Simulation) -> Vec<(IVec3, u8)> {
+        let mut cells: Vec<(IVec3, u8)> = simulation.cells().map(|(position, cell)| (position, cell.value)).collect();
+
+        cells.sort_by_key(|(position, _)| position.to_array());
+        cells
+    }
+
+    // step the way the viewer does, storing every generation ; returns the state and rng position of each generation
+    fn run(history: &mut History, simulation: &mut MultiThreaded, rng: &mut SeedRng, rule: &Rule) -> Vec<(Vec<(IVec3, u8)>, u128)> {
+        let mut states = vec![(state(simulation), rng.get_word_pos())];
+
+        history.push(simulation, None, rng.get_word_pos());
+        simulation.record_changes(true);
+
+        for _ in 0..GENERATIONS {
+            if rule.seeding.seeds_at(simulation.generation()) {
+                simulation.seed(rule, &rule.seeding.positions(simulation.center(), simulation.bounds(), rng));
+            }
+
+            simulation.step(rule);
+
+            let changes = simulation.take_changes();
+
+            history.push(simulation, Some(changes), rng.get_word_pos());
+            states.push((state(simulation), rng.get_word_pos()));
+        }
+
+        states
+    }
+
+    fn simulation(rule: &Rule) -> MultiThreaded {
+        let mut simulation = MultiThreaded::new();
+
+        simulation.set_bounds(rule.bounding_size);
+        simulation
+    }
+
+    #[test]
+    fn rewinds_to_exact_cells_and_rng_position() {
+        let rule = rule();
+
+        for mode in [HistoryMode::Full, HistoryMode::Delta(6)] {
+            let mut history = History::new(mode, usize::MAX);
+            let mut simulation = simulation(&rule);
+            let mut rng = SeedRng::seed_from_u64(5);
+            let states = run(&mut history, &mut simulation, &mut rng, &rule);
+
+            assert!(states[GENERATIONS].0.len() > 100);
+
+            for (generations, target) in [(1, GENERATIONS - 1), (13, GENERATIONS - 14), (7, GENERATIONS - 21)] {
+                let rng_position = history.rewind(generations, &mut simulation, &rule);
+
+                assert_eq!(simulation.generation(), target as u64);
+                assert_eq!(state(&simulation), states[target].0, "cells after rewinding to {}", target);
+                assert_eq!(rng_position, Some(states[target].1), "rng position after rewinding to {}", target);
+                assert_eq!(history.newest(), Some(target as u64));
+            }
+
+            // stepping on from the rewound generation gives the same generations again
+            let target = simulation.generation() as usize;
+            let mut rng = SeedRng::seed_from_u64(5);
+
+            rng.set_word_pos(states[target].1);
+
+            if rule.seeding.seeds_at(simulation.generation()) {
+                simulation.seed(&rule, &rule.seeding.positions(simulation.center(), simulation.bounds(), &mut rng));
+            }
+
+            simulation.step(&rule);
+
+            assert_eq!(state(&simulation), states[target + 1].0);
+        }
+    }
+
+    #[test]
+    fn budget_evicts_oldest_first() {
+        let rule = rule();
+
+        for mode in [HistoryMode::Full, HistoryMode::Delta(4)] {
+            let mut unbounded = History::new(mode, usize::MAX);
+            let mut simulation = simulation(&rule);
+
+            run(&mut unbounded, &mut simulation, &mut SeedRng::seed_from_u64(9), &rule);
+
+            let budget = unbounded.bytes() / 3;
+            let mut history = History::new(mode, budget);
+            let mut simulation = self::simulation(&rule);
+            let states = run(&mut history, &mut simulation, &mut SeedRng::seed_from_u64(9), &rule);
+            let oldest = history.oldest().unwrap() as usize;
+
+            assert!(history.bytes() <= budget);
+            assert!(oldest > 0);
+            assert_eq!(history.newest(), Some(GENERATIONS as u64));
+
+            // everything from the oldest kept generation on is still there
+            history.rewind(u64::MAX, &mut simulation, &rule);
+
+            assert_eq!(simulation.generation(), oldest as u64);
+            assert_eq!(state(&simulation), states[oldest].0);
+        }
+    }
+
+    #[test]
+    fn rewinding_past_the_start_does_nothing() {
+        let rule = rule();
+        let mut history = History::new(HistoryMode::Delta(6), usize::MAX);
+        let mut simulation = simulation(&rule);
+
+        assert_eq!(history.rewind(1, &mut simulation, &rule), None);
+
+        let states = run(&mut history, &mut simulation, &mut SeedRng::seed_from_u64(1), &rule);
+
+        assert_eq!(history.rewind(u64::MAX, &mut simulation, &rule), Some(states[0].1));
+        assert_eq!(simulation.generation(), 0);
+
+        // a simulation changed after reaching the start is left alone
+        simulation.seed(&rule, &[IVec3::ZERO]);
+
+        let seeded = state(&simulation);
+
+        assert_eq!(history.rewind(1, &mut simulation, &rule), None);
+        assert_eq!(history.rewind(0, &mut simulation, &rule), None);
+        assert_eq!(state(&simulation), seeded);
+        assert_eq!(history.oldest(), Some(0));
+        assert_eq!(history.newest(), Some(0));
+    }
+}
@@ -0,0 +1,29 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * February 20, 2023
+ */
+
+// simulation core without any rendering or input, shared by the viewer and headless tools
+
+pub mod boundary;
+pub mod chunks;
+pub mod colour;
+pub mod cycles;
+pub mod engine;
+pub mod generators;
+pub mod history;
+pub mod mesh;
+pub mod multi_threading;
+pub mod neighbours;
+pub mod pattern;
+pub mod recording;
+pub mod rule;
+pub mod run;
+pub mod seeding;
+pub mod snapshot;
+pub mod sparse;
+pub mod stats;
+pub mod utils;
+pub mod volume;
+pub mod vox;
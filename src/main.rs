@@ -8,32 +8,38 @@
 
 use bevy::{
     prelude::*,
-    render::view::NoFrustumCulling
+    render::view::NoFrustumCulling,
+    tasks::AsyncComputeTaskPool
+};
+
+use cellular_automata::{
+    multi_threading::MultiThreaded,
+    rule::Rule,
+    sparse::Sparse
 };
 
 mod cell_renderer;
 use cell_renderer::*;
 
-mod multi_threading;
-use multi_threading::MultiThreaded;
+mod clock;
 
-mod neighbours;
-use neighbours::NeighbourMethod;
+mod presets;
+use presets::{Presets, PresetsPlugin};
 
 mod rotating_camera;
 use rotating_camera::{RotatingCamera, RotatingCameraPlugin};
 
-mod rule;
-use rule::*;
+mod rule_file;
+use rule_file::{RuleFilePath, RuleFilePlugin};
 
 mod simulation;
 use simulation::{Simulations, SimulationsPlugin};
 
-mod utils;
-
 // setup simulation parameters
-fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut simulations: ResMut<Simulations>) {
-    simulations.add_simulation("Multi-threaded".into(), Box::new(MultiThreaded::new()));
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut simulations: ResMut<Simulations>,
+         task_pool: Res<AsyncComputeTaskPool>) {
+    simulations.add_simulation("Multi-threaded".into(), Box::new(MultiThreaded::with_task_pool(task_pool.0.clone())));
+    simulations.add_simulation("Sparse".into(), Box::new(Sparse::with_task_pool(task_pool.0.clone())));
 
     // default mesh, scale is set to 0.0 to hide until a simulation starts
     commands.spawn().insert_bundle((
@@ -62,88 +68,48 @@ fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut simulatio
 }
 
 fn main() {
-    let rule = Rule {
-        bounding_size: 50,
-
-        // builder
-        // survival_rule: Value::new(&[2, 6, 9]),
-        // birth_rule: Value::new(&[4, 6, 8, 9, 10]),
-        // states: 10,
-        // colour_method: ColourMethod::DistanceToCenter(Color::ORANGE_RED, Color::RED),
-        // neighbour_method: NeighbourMethod::Moore
-
-        // VonNeuman pyramid
-        // survival_rule: Value::from_range(0..=6),
-        // birth_rule: Value::new(&[1, 3]),
-        // states: 2,
-        // colour_method: ColourMethod::DistanceToCenter(Color::LIME_GREEN, Color::BLUE),
-        // neighbour_method: NeighbourMethod::VonNeuman
-
-        // fancy pattern
-        // survival_rule: Value::new(&[0, 1, 2, 3, 7, 8, 9, 11, 13, 18, 21, 22, 24, 26]),
-        // birth_rule: Value::new(&[4, 13, 17, 20, 21, 22, 23, 24, 26]),
-        // states: 4,
-        // colour_method: ColourMethod::StateLerp(Color::RED, Color::BLUE),
-        // neighbour_method: NeighbourMethod::Moore
-
-        // crystals
-        // survival_rule: Value::new(&[5, 6, 7, 8]),
-        // birth_rule: Value::new(&[6, 7, 9]),
-        // states: 10,
-        // colour_method: ColourMethod::DistanceToCenter(Color::GOLD, Color::BLUE),
-        // neighbour_method: NeighbourMethod::Moore
-
-        // swapping structures
-        // survival_rule: Value::new(&[3, 6, 9]),
-        // birth_rule: Value::new(&[4, 8, 10]),
-        // states: 20,
-        // colour_method: ColourMethod::StateLerp(Color::RED, Color::GREEN),
-        // neighbour_method: NeighbourMethod::Moore
-
-        // slowly expanding blob
-        survival_rule: Value::from_range(9..=26),
-        birth_rule: Value::new(&[5, 6, 7, 12, 13, 15]),
-        states: 20,
-        colour_method: ColourMethod::StateLerp(Color::BLUE, Color::RED),
-        neighbour_method: NeighbourMethod::Moore
-
-        // 445 rule
-        // survival_rule: Value::new(&[4]),
-        // birth_rule: Value::new(&[4]),
-        // states: 5,
-        // colour_method: ColourMethod::StateLerp(Color::BLACK, Color::RED),
-        // neighbour_method: NeighbourMethod::Moore
-
-        // expand, then die
-        // survival_rule: Value::new(&[4]),
-        // birth_rule: Value::new(&[3]),
-        // states: 20,
-        // colour_method: ColourMethod::StateLerp(Color::BLACK, Color::RED),
-        // neighbour_method: NeighbourMethod::Moore
-
-        // ???
-        // survival_rule: Value::new(&[6, 7]),
-        // birth_rule: Value::new(&[4, 6, 9, 10, 11]),
-        // states: 6,
-        // colour_method: ColourMethod::StateLerp(Color::BLUE, Color::RED),
-        // neighbour_method: NeighbourMethod::Moore
-
-        // large lines
-        // survival_rule: Value::new(&[5]),
-        // birth_rule: Value::new(&[4, 6, 9, 10, 11, 16, 17, 18, 19, 20, 21, 22, 23, 24]),
-        // states: 35,
-        // colour_method: ColourMethod::StateLerp(Color::BLUE, Color::RED),
-        // neighbour_method: NeighbourMethod::Moore
+    let presets = Presets::new();
+    let mut rule_file = None;
+
+    // rule may be given on the command line as a rule file, a preset name or in rule notation, e.g. '9-26/5-7,12-13,15/20/M'
+    let rule = match std::env::args().nth(1) {
+        Some(text) if text.ends_with(".rule") => {
+            rule_file = Some(RuleFilePath(text));
+
+            presets.select("slowly expanding blob").unwrap()
+        },
+        Some(text) => match presets.select(&text) {
+            Some(rule) => rule,
+            None => match text.parse::<Rule>() {
+                Ok(rule) => rule,
+                Err(error) => {
+                    eprintln!("invalid rule '{}': {}", text, error);
+                    eprintln!("available presets: {}", presets.names().collect::<Vec<_>>().join(", "));
+                    std::process::exit(1);
+                }
+            }
+        },
+        None => presets.select("slowly expanding blob").unwrap()
     };
 
+    if let Err(error) = rule.validate() {
+        eprintln!("invalid rule '{}': {}", rule, error);
+        std::process::exit(1);
+    }
+
     let mut task_pool_settings = DefaultTaskPoolOptions::default();
 
     task_pool_settings.async_compute.percent = 1.0f32;
     task_pool_settings.compute.percent = 0.0f32;
     task_pool_settings.io.percent = 0.0f32;
 
-    App::new()
-        .insert_resource(task_pool_settings)
+    let mut app = App::new();
+
+    if let Some(rule_file) = rule_file {
+        app.insert_resource(rule_file);
+    }
+
+    app.insert_resource(task_pool_settings)
         .insert_resource(WindowDescriptor {
             title: "3D Cellular Automata".into(),
             ..Default::default()
@@ -154,6 +120,9 @@ fn main() {
         .add_plugin(RotatingCameraPlugin)
         .add_plugin(CellMaterialPlugin)
         .insert_resource(rule)
+        .insert_resource(presets)
+        .add_plugin(PresetsPlugin)
+        .add_plugin(RuleFilePlugin)
         .add_plugin(SimulationsPlugin)
         .add_startup_system(setup)
         .run();
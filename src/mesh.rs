@@ -0,0 +1,427 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * March 14, 2023
+ */
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path
+};
+
+use bevy_math::{IVec3, Vec3};
+
+use crate::{
+    engine::Simulation,
+    rule::Rule
+};
+
+// face of a slice in the plane of an axis, as xy position along the other two axes
+type Face = (i32, i32);
+
+// axis, direction (+1 or -1) and slice a face faces out of
+type Slice = (usize, i32, i32);
+
+// surface of the live cells ; every cell is a unit cube centered on its position relative to the world's center
+#[derive(Default)]
+pub struct Mesh {
+    pub positions: Vec<Vec3>,
+    // RGBA colour of every vertex, averaged over the faces that meet there
+    pub colours: Vec<[u8; 4]>,
+    // counter clockwise when seen from outside
+    pub triangles: Vec<[u32; 3]>
+}
+
+// rectangle of merged faces with the colour they share, as counter clockwise corners in doubled coordinates
+struct Quad {
+    corners: [IVec3; 4],
+    colour: [u8; 4]
+}
+
+// convert position along axis, u and v into xyz
+fn unswizzle(axis: usize, a: i32, u: i32, v: i32) -> IVec3 {
+    let mut position = [0; 3];
+
+    position[axis] = a;
+    position[(axis + 1) % 3] = u;
+    position[(axis + 2) % 3] = v;
+
+    IVec3::from(position)
+}
+
+// merge faces of one slice with the same colour into as few rectangles as possible
+fn greedy(faces: &HashMap<Face, [u8; 4]>, mut f: impl FnMut(Face, Face, [u8; 4])) {
+    let mut order = faces.keys().copied().collect::<Vec<_>>();
+    let mut merged = HashSet::new();
+
+    order.sort_by_key(|(u, v)| (*v, *u));
+
+    for (u, v) in order {
+        if merged.contains(&(u, v)) {
+            continue;
+        }
+
+        let colour = faces[&(u, v)];
+        let free = |face: Face, merged: &HashSet<Face>| faces.get(&face) == Some(&colour) && !merged.contains(&face);
+
+        let mut width = 1;
+
+        while free((u + width, v), &merged) {
+            width += 1;
+        }
+
+        let mut height = 1;
+
+        while (0..width).all(|offset| free((u + offset, v + height), &merged)) {
+            height += 1;
+        }
+
+        for offset_v in 0..height {
+            for offset_u in 0..width {
+                merged.insert((u + offset_u, v + offset_v));
+            }
+        }
+
+        f((u, v), (u + width, v + height), colour);
+    }
+}
+
+impl Mesh {
+    // build greedy mesh of the outward faces of the live cells, coloured by the rule's colour method
+    pub fn from_simulation(simulation: &dyn Simulation, rule: &Rule) -> Mesh {
+        let center = simulation.center();
+        let cells: HashMap<IVec3, [u8; 4]> = simulation.cells()
+            .map(|(position, cell)| {
+                let colour = simulation.colour(rule, position, cell).as_rgba_u8();
+
+                (position - center, colour)
+            })
+            .collect();
+
+        Mesh::from_cells(&cells)
+    }
+
+    // build greedy mesh of the outward faces of cells at xyz positions with the given colours
+    pub fn from_cells(cells: &HashMap<IVec3, [u8; 4]>) -> Mesh {
+        // collect faces with no live cell on their other side, by the slice they lie in
+        let mut slices: HashMap<Slice, HashMap<Face, [u8; 4]>> = HashMap::new();
+
+        for (position, colour) in cells {
+            let array = position.to_array();
+
+            for axis in 0..3 {
+                for direction in [1, -1] {
+                    let mut neighbour = array;
+
+                    neighbour[axis] += direction;
+
+                    if !cells.contains_key(&IVec3::from(neighbour)) {
+                        slices.entry((axis, direction, array[axis])).or_default()
+                            .insert((array[(axis + 1) % 3], array[(axis + 2) % 3]), *colour);
+                    }
+                }
+            }
+        }
+
+        // merge faces ; cell corners lie on odd doubled coordinates so quad centers stay on the integer grid
+        let mut quads = vec![];
+
+        for ((axis, direction, slice), faces) in &slices {
+            let (axis, direction, plane) = (*axis, *direction, slice * 2 + direction);
+
+            greedy(faces, |(u0, v0), (u1, v1), colour| {
+                let (u0, v0, u1, v1) = (u0 * 2 - 1, v0 * 2 - 1, u1 * 2 - 1, v1 * 2 - 1);
+                let mut corners = [
+                    unswizzle(axis, plane, u0, v0),
+                    unswizzle(axis, plane, u1, v0),
+                    unswizzle(axis, plane, u1, v1),
+                    unswizzle(axis, plane, u0, v1)
+                ];
+
+                if direction < 0 {
+                    corners.reverse();
+                }
+
+                quads.push(Quad {
+                    corners,
+                    colour
+                });
+            });
+        }
+
+        let mut mesh = Mesh::default();
+        let mut vertices: HashMap<IVec3, u32> = HashMap::new();
+        let mut colour_sums: Vec<[u32; 5]> = vec![];
+
+        // share every corner between the quads meeting there
+        let mut vertex = |mesh: &mut Mesh, colour_sums: &mut Vec<[u32; 5]>, position: IVec3, colour: [u8; 4]| {
+            let index = *vertices.entry(position).or_insert_with(|| {
+                mesh.positions.push(position.as_vec3() / 2.0);
+                colour_sums.push([0; 5]);
+
+                (mesh.positions.len() - 1) as u32
+            });
+            let sum = &mut colour_sums[index as usize];
+
+            for (total, channel) in sum.iter_mut().zip(colour) {
+                *total += channel as u32;
+            }
+
+            sum[4] += 1;
+
+            index
+        };
+
+        let corners: HashSet<IVec3> = quads.iter().flat_map(|quad| quad.corners).collect();
+
+        for quad in &quads {
+            // corners of neighbouring quads lying on an edge become vertices of this quad too, so no T-junctions
+            // leave cracks and the surface stays watertight
+            let mut ring = vec![];
+
+            for (start, end) in quad.corners.iter().zip(quad.corners.iter().cycle().skip(1)) {
+                let step = (*end - *start).signum() * 2;
+                let mut position = *start;
+
+                while position != *end {
+                    if corners.contains(&position) {
+                        ring.push(vertex(&mut mesh, &mut colour_sums, position, quad.colour));
+                    }
+
+                    position += step;
+                }
+            }
+
+            if ring.len() == 4 {
+                mesh.triangles.push([ring[0], ring[1], ring[2]]);
+                mesh.triangles.push([ring[0], ring[2], ring[3]]);
+            } else {
+                let center = (quad.corners[0] + quad.corners[2]) / 2;
+                let center = vertex(&mut mesh, &mut colour_sums, center, quad.colour);
+
+                for (start, end) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+                    mesh.triangles.push([center, *start, *end]);
+                }
+            }
+        }
+
+        mesh.colours = colour_sums.iter().map(|sum| [0, 1, 2, 3].map(|channel| (sum[channel] / sum[4].max(1)) as u8)).collect();
+
+        mesh
+    }
+
+    // get outward unit normal of a triangle
+    fn normal(&self, triangle: &[u32; 3]) -> Vec3 {
+        let [a, b, c] = triangle.map(|index| self.positions[index as usize]);
+
+        (b - a).cross(c - a).normalize_or_zero()
+    }
+
+    // write Wavefront OBJ with vertex colours appended to every vertex
+    pub fn write_obj<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for (position, colour) in self.positions.iter().zip(&self.colours) {
+            writeln!(writer, "v {} {} {} {:.4} {:.4} {:.4}", position.x, position.y, position.z,
+                     colour[0] as f32 / 255.0, colour[1] as f32 / 255.0, colour[2] as f32 / 255.0)?;
+        }
+
+        for triangle in &self.triangles {
+            writeln!(writer, "f {} {} {}", triangle[0] + 1, triangle[1] + 1, triangle[2] + 1)?;
+        }
+
+        writer.flush()
+    }
+
+    // write binary STL, which has no colours
+    pub fn write_stl<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut header = [0u8; 80];
+        let title = b"3D Cellular Automata";
+
+        header[..title.len()].copy_from_slice(title);
+        writer.write_all(&header)?;
+        writer.write_all(&(self.triangles.len() as u32).to_le_bytes())?;
+
+        for triangle in &self.triangles {
+            let normal = self.normal(triangle);
+
+            for value in normal.to_array() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+
+            for index in triangle {
+                for value in self.positions[*index as usize].to_array() {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+
+            writer.write_all(&0u16.to_le_bytes())?;
+        }
+
+        writer.flush()
+    }
+
+    // write binary little endian PLY with vertex colours
+    pub fn write_ply<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "ply\nformat binary_little_endian 1.0\ncomment 3D Cellular Automata\n")?;
+        write!(writer, "element vertex {}\nproperty float x\nproperty float y\nproperty float z\n", self.positions.len())?;
+        write!(writer, "property uchar red\nproperty uchar green\nproperty uchar blue\nproperty uchar alpha\n")?;
+        write!(writer, "element face {}\nproperty list uchar uint vertex_indices\nend_header\n", self.triangles.len())?;
+
+        for (position, colour) in self.positions.iter().zip(&self.colours) {
+            for value in position.to_array() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+
+            writer.write_all(colour)?;
+        }
+
+        for triangle in &self.triangles {
+            writer.write_all(&[3])?;
+
+            for index in triangle {
+                writer.write_all(&index.to_le_bytes())?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    pub fn save_obj<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_obj(&mut BufWriter::new(File::create(path)?))
+    }
+
+    pub fn save_stl<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_stl(&mut BufWriter::new(File::create(path)?))
+    }
+
+    pub fn save_ply<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_ply(&mut BufWriter::new(File::create(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_math::ivec3;
+
+    use super::*;
+
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+    const RED: [u8; 4] = [255, 0, 0, 255];
+
+    fn mesh(cells: &[(IVec3, [u8; 4])]) -> Mesh {
+        Mesh::from_cells(&cells.iter().copied().collect())
+    }
+
+    // every edge is used once in each direction, by exactly two triangles winding the same way
+    fn assert_watertight(mesh: &Mesh) {
+        let mut edges: HashMap<(u32, u32), usize> = HashMap::new();
+
+        for triangle in &mesh.triangles {
+            for (start, end) in [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])] {
+                *edges.entry((start, end)).or_default() += 1;
+            }
+        }
+
+        for ((start, end), count) in &edges {
+            assert_eq!(*count, 1, "edge {} -> {} is used {} times", start, end, count);
+            assert_eq!(edges.get(&(*end, *start)), Some(&1), "edge {} -> {} has no twin", start, end);
+        }
+    }
+
+    #[test]
+    fn single_cell_gives_six_outward_quads() {
+        let mesh = mesh(&[(IVec3::ZERO, WHITE)]);
+
+        assert_eq!(mesh.positions.len(), 8);
+        assert_eq!(mesh.triangles.len(), 12);
+        assert!(mesh.positions.iter().all(|position| position.abs() == Vec3::splat(0.5)));
+
+        for triangle in &mesh.triangles {
+            let [a, b, c] = triangle.map(|index| mesh.positions[index as usize]);
+            let centroid = (a + b + c) / 3.0;
+
+            assert!(mesh.normal(triangle).dot(centroid) > 0.0);
+        }
+
+        assert!(mesh.colours.iter().all(|colour| *colour == WHITE));
+        assert_watertight(&mesh);
+    }
+
+    #[test]
+    fn block_merges_into_six_quads() {
+        let mut cells = vec![];
+
+        for z in 0..2 {
+            for y in 0..2 {
+                for x in 0..2 {
+                    cells.push((ivec3(x, y, z), WHITE));
+                }
+            }
+        }
+
+        let mesh = mesh(&cells);
+
+        assert_eq!(mesh.positions.len(), 8);
+        assert_eq!(mesh.triangles.len(), 12);
+        assert_watertight(&mesh);
+    }
+
+    #[test]
+    fn t_junctions_stay_watertight() {
+        // a bar with a cube on top, whose side faces end halfway along the bar's merged faces
+        let bar = mesh(&[(ivec3(-1, 0, 0), WHITE), (IVec3::ZERO, WHITE), (ivec3(1, 0, 0), WHITE), (IVec3::Y, WHITE)]);
+
+        assert_watertight(&bar);
+
+        // faces of a different colour split the block's sides, leaving corners on the edges of the merged faces
+        let mut cells = vec![];
+
+        for z in 0..3 {
+            for y in 0..3 {
+                for x in 0..3 {
+                    cells.push((ivec3(x, y, z), if (x, y, z) == (1, 2, 1) { RED } else { WHITE }));
+                }
+            }
+        }
+
+        cells.extend([(ivec3(1, 3, 1), WHITE), (ivec3(1, 4, 1), RED), (ivec3(3, 1, 1), RED), (ivec3(3, 1, 2), WHITE)]);
+
+        let block = mesh(&cells);
+
+        assert_watertight(&block);
+        assert!(block.triangles.len() > 12);
+    }
+
+    #[test]
+    fn writers_report_counts_in_headers() {
+        let mesh = mesh(&[(IVec3::ZERO, WHITE), (IVec3::X, RED)]);
+
+        let mut obj = vec![];
+
+        mesh.write_obj(&mut obj).unwrap();
+
+        let obj = String::from_utf8(obj).unwrap();
+
+        assert_eq!(obj.lines().filter(|line| line.starts_with("v ")).count(), mesh.positions.len());
+        assert_eq!(obj.lines().filter(|line| line.starts_with("f ")).count(), mesh.triangles.len());
+
+        let mut stl = vec![];
+
+        mesh.write_stl(&mut stl).unwrap();
+
+        assert_eq!(u32::from_le_bytes(stl[80..84].try_into().unwrap()) as usize, mesh.triangles.len());
+        assert_eq!(stl.len(), 84 + mesh.triangles.len() * 50);
+
+        let mut ply = vec![];
+
+        mesh.write_ply(&mut ply).unwrap();
+
+        let end = ply.windows(11).position(|window| window == b"end_header\n").unwrap() + 11;
+        let header = String::from_utf8(ply[..end].to_vec()).unwrap();
+
+        assert!(header.contains(&format!("element vertex {}\n", mesh.positions.len())));
+        assert!(header.contains(&format!("element face {}\n", mesh.triangles.len())));
+        assert_eq!(ply.len() - end, mesh.positions.len() * 16 + mesh.triangles.len() * 13);
+    }
+}
@@ -4,68 +4,129 @@
  * January 22, 2023
  */
 
-use bevy::{
-    input::Input,
-    math::{ivec3, IVec3},
-    prelude::KeyCode,
-    tasks::TaskPool
-};
+use std::collections::HashMap;
+
+use bevy_math::IVec3;
+use bevy_tasks::TaskPool;
 
 use futures_lite::future;
 
 use crate::{
-    cell_renderer::InstanceData,
+    boundary::BoundaryCell,
+    chunks::{CHUNK_CELL_COUNT, index_to_chunk_index, index_to_chunk_offset},
+    engine::{Changes, Simulation},
     rule::Rule,
-    simulation::Simulation,
     utils::{self}
 };
 
-use super::{
-    CHUNK_CELL_COUNT,
-    index_to_chunk_index,
-    index_to_chunk_offset
-};
-
-#[derive(Clone, Copy, Default)]
-struct Cell {
-    value: u8,
-    neighbours: u8
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub value: u8,
+    pub neighbours: u8
 }
 
 impl Cell {
-    fn is_dead(self) -> bool {
+    pub fn is_dead(self) -> bool {
         self.value == 0
     }
 }
 
-type Chunk = super::Chunk<Cell>;
-type Chunks = super::Chunks<Cell>;
+type Chunk = crate::chunks::Chunk<Cell>;
+type Chunks = crate::chunks::Chunks<Cell>;
+
+// offset of a cell within its chunk and whether its neighbour count goes up or down
+type HaloUpdate = (usize, bool);
+
+// neighbour updates for a chunk from its own cells and from cells across its border
+#[derive(Default)]
+struct NeighbourUpdates {
+    spawns: Vec<usize>,
+    deaths: Vec<usize>,
+    halo: Vec<Vec<HaloUpdate>>
+}
+
+impl NeighbourUpdates {
+    fn is_empty(&self) -> bool {
+        self.spawns.is_empty() && self.deaths.is_empty() && self.halo.is_empty()
+    }
+}
 
 pub struct MultiThreaded {
-    chunks: Chunks
+    tasks: TaskPool,
+    chunks: Chunks,
+    generation: u64,
+    active: Vec<bool>,
+    alive_boundary_applied: bool,
+    changes: Option<Changes>
+}
+
+// implement Default trait for MultiThreaded
+impl Default for MultiThreaded {
+    fn default() -> Self {
+        MultiThreaded::new()
+    }
 }
 
 impl MultiThreaded {
-    // create new MultiThreaded
+    // create new MultiThreaded with its own task pool
     pub fn new() -> Self {
+        MultiThreaded::with_task_pool(TaskPool::new())
+    }
+
+    // create new MultiThreaded that runs its chunk tasks on the given task pool
+    pub fn with_task_pool(tasks: TaskPool) -> Self {
         MultiThreaded {
-            chunks: Chunks::new()
+            tasks,
+            chunks: Chunks::new(),
+            generation: 0,
+            active: vec![],
+            alive_boundary_applied: false,
+            changes: None
         }
     }
 
-    pub fn set_bounds(&mut self, new_bounds: i32) -> i32 {
-        self.chunks.set_bounds(new_bounds)
+    pub fn set_bounds(&mut self, new_bounds: IVec3) -> IVec3 {
+        if new_bounds != self.bounds() {
+            self.chunks.set_bounds(new_bounds);
+            self.active = vec![true; self.chunks.chunk_count];
+            self.alive_boundary_applied = false;
+        }
+
+        self.bounds()
     }
 
-    pub fn bounds(&self) -> i32 {
+    pub fn bounds(&self) -> IVec3 {
         self.chunks.bounds()
     }
 
     // get xyz position of center
     pub fn center(&self) -> IVec3 {
-        let center = self.bounds() / 2;
+        utils::center(self.bounds())
+    }
+
+    // get cell at xyz position, if it lies within bounds
+    pub fn cell(&self, position: IVec3) -> Option<Cell> {
+        if position.cmplt(IVec3::ZERO).any() || position.cmpge(self.bounds()).any() {
+            return None;
+        }
 
-        ivec3(center, center, center)
+        let index = self.chunks.position_to_index(position);
+
+        Some(self.chunks.chunks[index_to_chunk_index(index)].0[index_to_chunk_offset(index)])
+    }
+
+    // iterate over xyz position and state of every live cell
+    pub fn cells(&self) -> impl Iterator<Item = (IVec3, Cell)> + '_ {
+        self.chunks.chunks.iter().enumerate().flat_map(move |(chunk_index, chunk)| {
+            chunk.0.iter().enumerate()
+                .filter(|(_, cell)| !cell.is_dead())
+                .map(move |(offset, cell)| (self.chunks.index_to_position(chunk_index * CHUNK_CELL_COUNT + offset), *cell))
+        })
+    }
+
+    // get number of generations stepped since the simulation was cleared
+    pub fn generation(&self) -> u64 {
+        self.generation
     }
 
     pub fn cell_count(&self) -> usize {
@@ -82,12 +143,12 @@ impl MultiThreaded {
         count
     }
 
-    // wrap xyz position around the bounds if it is going out of bounds using helper function
-    fn wrap(&self, position: IVec3) -> IVec3 {
-        utils::wrap(position, self.bounds())
+    // apply the rule's boundaries to xyz position if it is going out of bounds
+    fn resolve(&self, rule: &Rule, position: IVec3) -> BoundaryCell {
+        rule.boundary.resolve(position, self.bounds())
     }
 
-    fn update_neighbours_chunk(chunk: &mut Chunk, rule: &Rule, offset: usize, increment: bool) {
+    pub fn update_neighbours_chunk(chunk: &mut Chunk, rule: &Rule, offset: usize, increment: bool) {
         let position = Chunk::index_to_position(offset);
 
         for dir in rule.neighbour_method.get_neighbour_iter() {
@@ -103,11 +164,15 @@ impl MultiThreaded {
         }
     }
 
-    fn update_neighbours(&self, chunks: &mut Vec<Chunk>, rule: &Rule, index: usize, increment: bool) {
+    fn update_neighbours(&self, chunks: &mut [Chunk], active: &mut [bool], rule: &Rule, index: usize, increment: bool) {
         let position = self.chunks.index_to_position(index);
 
         for dir in rule.neighbour_method.get_neighbour_iter() {
-            let neighbour_position = self.wrap(position + *dir);
+            // cells beyond a fixed boundary are never counted as neighbours here
+            let neighbour_position = match self.resolve(rule, position + *dir) {
+                BoundaryCell::Inside(neighbour_position) => neighbour_position,
+                BoundaryCell::Dead | BoundaryCell::Alive => continue
+            };
             let index = self.chunks.position_to_index(neighbour_position);
             let chunk = index_to_chunk_index(index);
             let offset = index_to_chunk_offset(index);
@@ -117,17 +182,31 @@ impl MultiThreaded {
             } else {
                 chunks[chunk].0[offset].neighbours -= 1;
             }
+
+            active[chunk] = true;
         }
     }
 
-    fn update_values_chunk(chunk: &mut Chunk, chunk_index: usize, rule: &Rule, chunk_spawns: &mut Vec<usize>, spawns: &mut Vec<usize>,
-                           chunk_deaths: &mut Vec<usize>, deaths: &mut Vec<usize>) {
+    // returns true if any cell of the chunk changed value ; false otherwise
+    #[allow(clippy::too_many_arguments)]
+    fn update_values_chunk(chunk: &mut Chunk, chunk_index: usize, limit: IVec3, rule: &Rule, chunk_spawns: &mut Vec<usize>,
+                           spawns: &mut Vec<usize>, chunk_deaths: &mut Vec<usize>, deaths: &mut Vec<usize>) -> bool {
+        let mut changed = false;
+
         for (offset, cell) in chunk.0.iter_mut().enumerate() {
+            let position = Chunk::index_to_position(offset);
+
+            // skip cells of the chunk that lie outside the bounds
+            if !Chunk::contains_position(position, limit) {
+                continue;
+            }
+
             if cell.is_dead() {
                 if rule.birth_rule.in_range(cell.neighbours) {
                     cell.value = rule.states;
+                    changed = true;
 
-                    if Chunk::is_border_position(Chunk::index_to_position(offset), 0) {
+                    if Chunk::is_border_position(position, limit) {
                         spawns.push(chunk_index * CHUNK_CELL_COUNT + offset);
                     } else {
                         chunk_spawns.push(offset);
@@ -136,7 +215,7 @@ impl MultiThreaded {
             } else {
                 if cell.value < rule.states || !rule.survival_rule.in_range(cell.neighbours) {
                     if cell.value == rule.states {
-                        if Chunk::is_border_position(Chunk::index_to_position(offset), 0) {
+                        if Chunk::is_border_position(position, limit) {
                             deaths.push(chunk_index * CHUNK_CELL_COUNT + offset);
                         } else {
                             chunk_deaths.push(offset);
@@ -144,150 +223,446 @@ impl MultiThreaded {
                     }
 
                     cell.value -= 1;
+                    changed = true;
+                }
+            }
+        }
+
+        changed
+    }
+
+    // bucket the neighbour updates of border spawns + deaths by the chunk they reach
+    fn bucket_halo(rule: &Rule, chunk_dimensions: IVec3, bounds: IVec3, spawns: &[usize], deaths: &[usize]) -> Vec<(usize, Vec<HaloUpdate>)> {
+        let mut buckets: HashMap<usize, Vec<HaloUpdate>> = HashMap::new();
+
+        for (indices, increment) in [(spawns, true), (deaths, false)] {
+            for index in indices {
+                let position = Chunks::index_to_position_ex(*index, chunk_dimensions);
+
+                for dir in rule.neighbour_method.get_neighbour_iter() {
+                    let neighbour_position = match rule.boundary.resolve(position + *dir, bounds) {
+                        BoundaryCell::Inside(neighbour_position) => neighbour_position,
+                        BoundaryCell::Dead | BoundaryCell::Alive => continue
+                    };
+                    let index = Chunks::position_to_index_ex(neighbour_position, chunk_dimensions);
+
+                    buckets.entry(index_to_chunk_index(index)).or_default().push((index_to_chunk_offset(index), increment));
                 }
             }
         }
+
+        buckets.into_iter().collect()
     }
 
-    pub fn update(&mut self, rule: &Rule, tasks: &TaskPool) {
+    // advance simulation by one generation
+    pub fn step(&mut self, rule: &Rule) {
+        if !self.alive_boundary_applied {
+            self.apply_alive_boundary(rule);
+        }
+
+        let tasks = &self.tasks;
         let mut chunks = std::mem::take(&mut self.chunks.chunks);
+        let mut active = std::mem::take(&mut self.active);
+        let chunk_dimensions = self.chunks.chunk_dimensions;
+        let bounds = self.bounds();
 
-        // update values
+        // update values of active chunks ; inactive chunks cannot change so their cells stay in place
         let mut value_tasks = vec![];
-        for (chunk_index, mut chunk) in chunks.into_iter().enumerate() {
+        for (chunk_index, chunk) in chunks.iter_mut().enumerate() {
+            if !active[chunk_index] {
+                continue;
+            }
+
+            let mut chunk = crate::chunks::Chunk(std::mem::take(&mut chunk.0));
             let rule = rule.clone();
+            let limit = self.chunks.chunk_limit(chunk_index);
             let mut chunk_spawns = vec![];
             let mut chunk_deaths = vec![];
             let mut spawns = vec![];
             let mut deaths = vec![];
 
-            value_tasks.push(tasks.spawn(async move {
-                Self::update_values_chunk(&mut chunk, chunk_index, &rule, &mut chunk_spawns, &mut spawns, &mut chunk_deaths, &mut deaths);
+            value_tasks.push((chunk_index, tasks.spawn(async move {
+                let changed = Self::update_values_chunk(&mut chunk, chunk_index, limit, &rule, &mut chunk_spawns, &mut spawns,
+                                                        &mut chunk_deaths, &mut deaths);
+                let halo = Self::bucket_halo(&rule, chunk_dimensions, bounds, &spawns, &deaths);
 
-                (chunk, chunk_spawns, spawns, chunk_deaths, deaths)
-            }));
+                (chunk, changed, chunk_spawns, chunk_deaths, spawns, deaths, halo)
+            })));
         }
 
-        // collect spawns + deaths
-        chunks = vec![];
+        // collect spawns + deaths ; a chunk stays active while its cells keep changing or its neighbours change
+        let mut updates = (0..chunks.len()).map(|_| NeighbourUpdates::default()).collect::<Vec<_>>();
+
+        for (chunk_index, task) in value_tasks {
+            let (chunk, changed, spawns, deaths, border_spawns, border_deaths, halo) = future::block_on(task);
 
-        let mut chunk_spawns = vec![];
-        let mut chunk_deaths = vec![];
-        let mut spawns = vec![];
-        let mut deaths = vec![];
+            if let Some(changes) = &mut self.changes {
+                let origin = chunk_index * CHUNK_CELL_COUNT;
 
-        for task in value_tasks {
-            let (chunk, in_spawns, out_spawns, in_deaths, out_deaths) = future::block_on(task);
+                changes.births.extend(spawns.iter().map(|offset| self.chunks.index_to_position(origin + offset)));
+                changes.births.extend(border_spawns.iter().map(|index| self.chunks.index_to_position(*index)));
+                changes.deaths.extend(deaths.iter().map(|offset| self.chunks.index_to_position(origin + offset)));
+                changes.deaths.extend(border_deaths.iter().map(|index| self.chunks.index_to_position(*index)));
+            }
 
-            chunks.push(chunk);
-            chunk_spawns.push(in_spawns);
-            chunk_deaths.push(in_deaths);
-            spawns.extend(out_spawns);
-            deaths.extend(out_deaths);
+            chunks[chunk_index] = chunk;
+            active[chunk_index] = changed;
+            updates[chunk_index].spawns = spawns;
+            updates[chunk_index].deaths = deaths;
+
+            for (target_index, halo_updates) in halo {
+                updates[target_index].halo.push(halo_updates);
+            }
         }
 
-        // update neighbours in parallel
+        // mark chunks reached across their border only once every chunk has reported, so a chunk reporting later
+        // cannot clear it
+        for (chunk_index, updates) in updates.iter().enumerate() {
+            if !updates.halo.is_empty() {
+                active[chunk_index] = true;
+            }
+        }
+
+        // update neighbours in parallel, each chunk applying its own changes and those reaching it across its border
         let mut neighbour_tasks = vec![];
 
-        for ((mut chunk, spawns), deaths) in chunks.into_iter().zip(chunk_spawns).zip(chunk_deaths) {
+        for (chunk_index, updates) in updates.into_iter().enumerate() {
+            if updates.is_empty() {
+                continue;
+            }
+
+            let mut chunk = crate::chunks::Chunk(std::mem::take(&mut chunks[chunk_index].0));
             let rule = rule.clone();
 
-            neighbour_tasks.push(tasks.spawn(async move {
-                for offset in spawns {
+            neighbour_tasks.push((chunk_index, tasks.spawn(async move {
+                for offset in updates.spawns {
                     Self::update_neighbours_chunk(&mut chunk, &rule, offset, true);
                 }
 
-                for offset in deaths {
+                for offset in updates.deaths {
                     Self::update_neighbours_chunk(&mut chunk, &rule, offset, false);
                 }
 
+                for (offset, increment) in updates.halo.into_iter().flatten() {
+                    if increment {
+                        chunk.0[offset].neighbours += 1;
+                    } else {
+                        chunk.0[offset].neighbours -= 1;
+                    }
+                }
+
                 chunk
-            }));
+            })));
         }
 
         // collect chunks
-        chunks = vec![];
+        for (chunk_index, task) in neighbour_tasks {
+            chunks[chunk_index] = future::block_on(task);
+        }
 
-        for task in neighbour_tasks {
-            let chunk = future::block_on(task);
+        self.chunks.chunks = chunks;
+        self.active = active;
+        self.generation += 1;
+    }
 
-            chunks.push(chunk);
-        }
+    // count cells beyond an alive boundary as permanent neighbours of the cells along it
+    fn apply_alive_boundary(&mut self, rule: &Rule) {
+        for chunk_index in 0..self.chunks.chunk_count {
+            let limit = self.chunks.chunk_limit(chunk_index);
 
-        // update neighbours in serial
-        for index in spawns {
-            self.update_neighbours(&mut chunks, rule, index, true);
-        }
+            for offset in 0..CHUNK_CELL_COUNT {
+                let chunk_position = Chunk::index_to_position(offset);
+
+                if !Chunk::contains_position(chunk_position, limit) || !Chunk::is_border_position(chunk_position, limit) {
+                    continue;
+                }
 
-        for index in deaths {
-            self.update_neighbours(&mut chunks, rule, index, false);
+                let position = self.chunks.index_to_position(chunk_index * CHUNK_CELL_COUNT + offset);
+                let alive = rule.neighbour_method.get_neighbour_iter().iter()
+                    .filter(|dir| self.resolve(rule, position + **dir) == BoundaryCell::Alive)
+                    .count();
+
+                self.chunks.chunks[chunk_index].0[offset].neighbours += alive as u8;
+            }
         }
 
-        self.chunks.chunks = chunks;
+        self.alive_boundary_applied = true;
     }
 
-    // spawn noise using given rule
-    pub fn spawn_noise(&mut self, rule: &Rule) {
+    // set cells at xyz positions alive using given rule ; positions beyond a fixed boundary are discarded
+    pub fn seed(&mut self, rule: &Rule, positions: &[IVec3]) {
         let mut chunks = std::mem::take(&mut self.chunks.chunks);
-
-        utils::spawn_noise_default(self.center(), |position| {
-            let index = self.chunks.position_to_index(self.wrap(position));
+        let mut active = std::mem::take(&mut self.active);
+
+        for position in positions {
+            let position = match self.resolve(rule, *position) {
+                BoundaryCell::Inside(position) => position,
+                BoundaryCell::Dead | BoundaryCell::Alive => continue
+            };
+            let index = self.chunks.position_to_index(position);
             let chunk = index_to_chunk_index(index);
             let offset = index_to_chunk_offset(index);
             let cell = &mut chunks[chunk].0[offset];
 
             if cell.is_dead() {
                 cell.value = rule.states;
-                self.update_neighbours(&mut chunks, rule, index, true);
+                active[chunk] = true;
+                self.update_neighbours(&mut chunks, &mut active, rule, index, true);
+
+                if let Some(changes) = &mut self.changes {
+                    changes.births.push(position);
+                }
             }
-        });
+        }
 
         self.chunks.chunks = chunks;
+        self.active = active;
     }
-}
 
-impl Simulation for MultiThreaded {
-    // step forward in simulation only if user presses 'S'
-    fn update(&mut self, input: &Input<KeyCode>, rule: &Rule, task_pool: &TaskPool) {
-        if !input.pressed(KeyCode::S) {
-            return;
+    // kill every cell, keeping bounds and whether changes are collected
+    pub fn clear(&mut self) {
+        let bounds = self.bounds();
+        let recording = self.changes.is_some();
+
+        *self = MultiThreaded::with_task_pool(self.tasks.clone());
+        self.set_bounds(bounds);
+        self.record_changes(recording);
+    }
+
+    pub fn record_changes(&mut self, enabled: bool) {
+        if enabled != self.changes.is_some() {
+            self.changes = enabled.then(Changes::default);
         }
+    }
 
-        self.spawn_noise(rule);
-        self.update(rule, task_pool);
+    // take births + deaths collected since the last call
+    pub fn take_changes(&mut self) -> Changes {
+        self.changes.as_mut().map(std::mem::take).unwrap_or_default()
     }
 
-    fn render(&self, rule: &Rule, data: &mut Vec<InstanceData>) {
-        for (chunk_index, chunk) in self.chunks.chunks.iter().enumerate() {
-            for (index, cell) in chunk.0.iter().enumerate() {
-                if cell.is_dead() {
-                    continue;
-                }
+    // replace every cell with the given values ; only cells in their first state count as neighbours
+    pub fn restore(&mut self, rule: &Rule, cells: &[(IVec3, u8)], generation: u64) {
+        self.clear();
+
+        let mut chunks = std::mem::take(&mut self.chunks.chunks);
+        let mut active = std::mem::take(&mut self.active);
+
+        for (position, value) in cells {
+            let position = match self.resolve(rule, *position) {
+                BoundaryCell::Inside(position) => position,
+                BoundaryCell::Dead | BoundaryCell::Alive => continue
+            };
+            let index = self.chunks.position_to_index(position);
+            let cell = &mut chunks[index_to_chunk_index(index)].0[index_to_chunk_offset(index)];
+
+            if !cell.is_dead() {
+                continue;
+            }
 
-                let position = self.chunks.index_to_position(chunk_index * CHUNK_CELL_COUNT + index);
+            cell.value = *value;
 
-                data.push(InstanceData {
-                    position: (position - self.center()).as_vec3(),
-                    scale: 1.0,
-                    colour: rule.colour_method.colour(rule.states, cell.value, cell.neighbours, utils::distance_to_center(position, self.bounds())).as_rgba_f32()
-                });
+            if *value == rule.states {
+                self.update_neighbours(&mut chunks, &mut active, rule, index, true);
             }
         }
+
+        self.chunks.chunks = chunks;
+        self.active = active;
+        self.generation = generation;
+    }
+
+    // set the values of cells in place ; only cells entering or leaving their first state change neighbour counts
+    pub fn set_cells(&mut self, rule: &Rule, cells: &[(IVec3, u8)], generation: u64) {
+        let mut chunks = std::mem::take(&mut self.chunks.chunks);
+        let mut active = std::mem::take(&mut self.active);
+
+        for (position, value) in cells {
+            let position = match self.resolve(rule, *position) {
+                BoundaryCell::Inside(position) => position,
+                BoundaryCell::Dead | BoundaryCell::Alive => continue
+            };
+            let index = self.chunks.position_to_index(position);
+            let chunk = index_to_chunk_index(index);
+            let cell = &mut chunks[chunk].0[index_to_chunk_offset(index)];
+            let previous = cell.value;
+
+            cell.value = *value;
+            active[chunk] = true;
+
+            if previous != rule.states && *value == rule.states {
+                self.update_neighbours(&mut chunks, &mut active, rule, index, true);
+            } else if previous == rule.states && *value != rule.states {
+                self.update_neighbours(&mut chunks, &mut active, rule, index, false);
+            }
+        }
+
+        self.chunks.chunks = chunks;
+        self.active = active;
+        self.generation = generation;
+    }
+}
+
+impl Simulation for MultiThreaded {
+    fn step(&mut self, rule: &Rule) {
+        self.step(rule);
+    }
+
+    fn seed(&mut self, rule: &Rule, positions: &[IVec3]) {
+        self.seed(rule, positions);
+    }
+
+    fn clear(&mut self) {
+        self.clear();
+    }
+
+    fn restore(&mut self, rule: &Rule, cells: &[(IVec3, u8)], generation: u64) {
+        self.restore(rule, cells, generation);
+    }
+
+    fn set_cells(&mut self, rule: &Rule, cells: &[(IVec3, u8)], generation: u64) {
+        self.set_cells(rule, cells, generation);
+    }
+
+    fn record_changes(&mut self, enabled: bool) {
+        self.record_changes(enabled);
     }
 
-    fn reset(&mut self) {
-        *self = MultiThreaded::new();
+    fn take_changes(&mut self) -> Changes {
+        self.take_changes()
+    }
+
+    fn cells(&self) -> Box<dyn Iterator<Item = (IVec3, Cell)> + '_> {
+        Box::new(self.cells())
     }
 
     fn cell_count(&self) -> usize {
         self.cell_count()
     }
 
-    fn set_bounds(&mut self, new_bounds: i32) -> i32 {
+    fn generation(&self) -> u64 {
+        self.generation()
+    }
+
+    fn set_bounds(&mut self, new_bounds: IVec3) -> IVec3 {
         self.set_bounds(new_bounds)
     }
 
-    fn bounds(&self) -> i32 {
+    fn bounds(&self) -> IVec3 {
         self.bounds()
     }
+
+    fn center(&self) -> IVec3 {
+        self.center()
+    }
+
+    fn grid(&self) -> Vec<Cell> {
+        self.chunks.to_grid()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_math::{const_ivec3, ivec3};
+
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        boundary::{Boundaries, Boundary},
+        neighbours::NeighbourMethod,
+        rule::Value,
+        seeding::SeedRng
+    };
+
+    // spans two chunks on every axis without filling the second, so halos cross into partial chunks too
+    const BOUNDS: IVec3 = const_ivec3!([34, 33, 33]);
+    const GENERATIONS: usize = 16;
+
+    // count neighbours of every cell from scratch ; a boundary that resolves onto a cell counts it once per direction
+    fn reference_neighbours(values: &[u8], rule: &Rule) -> Vec<u8> {
+        (0..values.len()).map(|index| {
+            let position = utils::index_to_position(index, BOUNDS);
+
+            rule.neighbour_method.get_neighbour_iter().iter()
+                .filter(|dir| match rule.boundary.resolve(position + **dir, BOUNDS) {
+                    BoundaryCell::Inside(neighbour) => values[utils::position_to_index(neighbour, BOUNDS)] == rule.states,
+                    BoundaryCell::Alive => true,
+                    BoundaryCell::Dead => false
+                })
+                .count() as u8
+        }).collect()
+    }
+
+    // serial step over the whole grid, the way the engine worked before chunks exchanged halos
+    fn reference_step(values: &[u8], neighbours: &[u8], rule: &Rule) -> Vec<u8> {
+        values.iter().zip(neighbours.iter().copied()).map(|(value, neighbours)| {
+            if *value == 0 {
+                if rule.birth_rule.in_range(neighbours) { rule.states } else { 0 }
+            } else if *value < rule.states || !rule.survival_rule.in_range(neighbours) {
+                value - 1
+            } else {
+                *value
+            }
+        }).collect()
+    }
+
+    fn assert_matches(engine: &MultiThreaded, values: &[u8], neighbours: &[u8], context: &str) {
+        let grid = engine.grid();
+
+        for (index, cell) in grid.iter().enumerate() {
+            let position = utils::index_to_position(index, BOUNDS);
+
+            assert_eq!(cell.value, values[index], "value at {} differs, {}", position, context);
+            assert_eq!(cell.neighbours, neighbours[index], "neighbours at {} differ, {}", position, context);
+        }
+    }
+
+    #[test]
+    fn halo_exchange_matches_serial_reference() {
+        let rules = [
+            (NeighbourMethod::Moore, Value::from_range(9..=26).unwrap(), Value::new(&[5, 6, 7, 12, 13, 15]).unwrap(), 5),
+            (NeighbourMethod::VonNeuman, Value::new(&[1, 2, 3]).unwrap(), Value::new(&[1, 3]).unwrap(), 3)
+        ];
+        let boundaries = [Boundary::Periodic, Boundary::Dead, Boundary::Alive, Boundary::Reflective];
+
+        for (neighbour_method, survival_rule, birth_rule, states) in rules {
+            for boundary in boundaries {
+                let rule = Rule {
+                    survival_rule,
+                    birth_rule,
+                    states,
+                    bounding_size: BOUNDS,
+                    neighbour_method: neighbour_method.clone(),
+                    boundary: Boundaries::all(boundary),
+                    ..Default::default()
+                };
+                let mut rng = SeedRng::seed_from_u64(42);
+                let positions = (0..BOUNDS.x * BOUNDS.y * BOUNDS.z / 6)
+                    .map(|_| ivec3(rng.gen_range(0..BOUNDS.x), rng.gen_range(0..BOUNDS.y), rng.gen_range(0..BOUNDS.z)))
+                    .collect::<Vec<_>>();
+
+                let mut engine = MultiThreaded::new();
+                let mut values = vec![0; (BOUNDS.x * BOUNDS.y * BOUNDS.z) as usize];
+
+                engine.set_bounds(BOUNDS);
+                engine.seed(&rule, &positions);
+
+                for position in &positions {
+                    values[utils::position_to_index(*position, BOUNDS)] = rule.states;
+                }
+
+                let mut neighbours = reference_neighbours(&values, &rule);
+
+                for generation in 1..=GENERATIONS {
+                    engine.step(&rule);
+                    values = reference_step(&values, &neighbours, &rule);
+                    neighbours = reference_neighbours(&values, &rule);
+
+                    let context = format!("generation {} of {} with {:?} boundaries", generation, rule, boundary);
+
+                    assert_matches(&engine, &values, &neighbours, &context);
+                }
+            }
+        }
+    }
 }
@@ -4,10 +4,11 @@
  * January 20, 2023
  */
 
-use bevy::math::{const_ivec3, IVec3};
+use bevy_math::{const_ivec3, IVec3};
 
-#[allow(dead_code)]
-#[derive(Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
 pub enum NeighbourMethod {
     Moore,
     VonNeuman
@@ -20,6 +21,11 @@ impl NeighbourMethod {
             NeighbourMethod::VonNeuman => &VONNEUMAN_NEIGHBOURS[..]
         }
     }
+
+    // get number of neighbours a cell has
+    pub fn neighbour_count(&self) -> u8 {
+        self.get_neighbour_iter().len() as u8
+    }
 }
 
 // Moore neighbours method constants
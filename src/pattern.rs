@@ -0,0 +1,457 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * March 10, 2023
+ */
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs,
+    io,
+    path::Path,
+    str::FromStr
+};
+
+use bevy_math::{ivec3, IVec3};
+
+use crate::{
+    engine::Simulation,
+    rule::{Rule, RuleParseError}
+};
+
+// longest line written before wrapping, so patterns paste cleanly
+const LINE_LENGTH: usize = 70;
+
+// number of decaying states each letter prefix covers
+const LETTERS: u8 = 24;
+
+// sparse 3D pattern in a run length encoded text format, e.g.
+//
+//     #N glider
+//     x = 3, y = 3, z = 2, rule = 4-5/5/2/M
+//     bo$2o/o$b2o!
+//
+// 'b' is a dead cell, 'o' a cell in the rule's first state and 'A'-'X' / 'pA'-'yX' the decaying states,
+// '$' ends a row along x, '/' ends a layer along z and '!' ends the pattern ; each may be preceded by a run count
+#[derive(Clone)]
+pub struct Pattern {
+    pub size: IVec3,
+    pub rule: Rule,
+    // xyz position within the bounding box and value of every live cell
+    pub cells: Vec<(IVec3, u8)>
+}
+
+#[derive(Debug)]
+pub enum PatternError {
+    Io(io::Error),
+    MissingHeader,
+    InvalidHeader(String),
+    InvalidRule(RuleParseError),
+    UnexpectedCharacter(char),
+    OutOfBounds(IVec3),
+    InvalidState(u32),
+    RunTooLong,
+    UnterminatedRun
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatternError::Io(error) => write!(f, "{}", error),
+            PatternError::MissingHeader => write!(f, "expected header 'x = .., y = .., z = .., rule = ..'"),
+            PatternError::InvalidHeader(field) => write!(f, "'{}' is not a valid header field", field),
+            PatternError::InvalidRule(error) => write!(f, "invalid rule: {}", error),
+            PatternError::UnexpectedCharacter(character) => write!(f, "unexpected character '{}'", character),
+            PatternError::OutOfBounds(position) => write!(f, "cell at {} lies outside the pattern's bounding box", position),
+            PatternError::InvalidState(value) => write!(f, "state {} is not allowed by the pattern's rule", value),
+            PatternError::RunTooLong => write!(f, "run count or position does not fit in 32 bits"),
+            PatternError::UnterminatedRun => write!(f, "pattern ends after a run count or state prefix without its cell")
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl From<io::Error> for PatternError {
+    fn from(error: io::Error) -> Self {
+        PatternError::Io(error)
+    }
+}
+
+impl Pattern {
+    // build pattern from live cells, keeping only those within min (inclusive) and max (exclusive) if a region is given
+    pub fn from_cells<I: Iterator<Item = (IVec3, u8)>>(cells: I, rule: &Rule, region: Option<(IVec3, IVec3)>) -> Pattern {
+        let cells = cells
+            .filter(|(position, _)| region.is_none_or(|(min, max)| position.cmpge(min).all() && position.cmplt(max).all()))
+            .collect::<Vec<_>>();
+
+        if cells.is_empty() {
+            return Pattern {
+                size: IVec3::ZERO,
+                rule: rule.clone(),
+                cells
+            };
+        }
+
+        let min = cells.iter().fold(IVec3::splat(i32::MAX), |min, (position, _)| min.min(*position));
+        let max = cells.iter().fold(IVec3::splat(i32::MIN), |max, (position, _)| max.max(*position));
+
+        Pattern {
+            size: max - min + IVec3::ONE,
+            rule: rule.clone(),
+            cells: cells.into_iter().map(|(position, value)| (position - min, value)).collect()
+        }
+    }
+
+    // build pattern from the live cells of a simulation
+    pub fn from_simulation(simulation: &dyn Simulation, rule: &Rule, region: Option<(IVec3, IVec3)>) -> Pattern {
+        Pattern::from_cells(simulation.cells().map(|(position, cell)| (position, cell.value)), rule, region)
+    }
+
+    // set cells of the pattern with its minimum corner at xyz position, keeping the other cells of the simulation ;
+    // returns true if the cells were seeded, so they show up in the simulation's changes like any other seed, and
+    // false if decaying cells in the pattern made it rebuild the whole world instead
+    pub fn stamp(&self, simulation: &mut dyn Simulation, rule: &Rule, position: IVec3) -> bool {
+        if self.cells.iter().all(|(_, value)| *value == self.rule.states) {
+            let positions = self.cells.iter().map(|(offset, _)| position + *offset).collect::<Vec<_>>();
+
+            simulation.seed(rule, &positions);
+
+            return true;
+        }
+
+        let mut cells: HashMap<IVec3, u8> = simulation.cells().map(|(position, cell)| (position, cell.value)).collect();
+
+        for (offset, value) in &self.cells {
+            // cells in the pattern's first state stay in the first state of the running rule
+            let value = if *value == self.rule.states { rule.states } else { (*value).min(rule.states) };
+
+            cells.insert(position + *offset, value);
+        }
+
+        let cells = cells.into_iter().collect::<Vec<_>>();
+        let generation = simulation.generation();
+
+        simulation.restore(rule, &cells, generation);
+
+        false
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PatternError> {
+        Ok(fs::write(path, self.to_string())?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Pattern, PatternError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    // get token of a cell value
+    fn state_token(&self, value: u8) -> String {
+        if value == 0 {
+            return "b".into();
+        }
+
+        if value == self.rule.states {
+            return "o".into();
+        }
+
+        let prefix = (value - 1) / LETTERS;
+        let letter = (b'A' + (value - 1) % LETTERS) as char;
+
+        if prefix == 0 {
+            letter.to_string()
+        } else {
+            format!("{}{}", (b'p' + prefix - 1) as char, letter)
+        }
+    }
+}
+
+// write runs of equal tokens, wrapping lines
+struct RunWriter {
+    lines: Vec<String>,
+    token: String,
+    count: usize
+}
+
+impl RunWriter {
+    fn push(&mut self, token: &str, count: usize) {
+        if count == 0 {
+            return;
+        }
+
+        if self.token == token {
+            self.count += count;
+            return;
+        }
+
+        self.flush();
+        self.token = token.into();
+        self.count = count;
+    }
+
+    fn flush(&mut self) {
+        if self.count == 0 {
+            return;
+        }
+
+        let run = if self.count == 1 { self.token.clone() } else { format!("{}{}", self.count, self.token) };
+
+        match self.lines.last_mut() {
+            Some(line) if line.len() + run.len() <= LINE_LENGTH => line.push_str(&run),
+            _ => self.lines.push(run)
+        }
+
+        self.count = 0;
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "x = {}, y = {}, z = {}, rule = {}", self.size.x, self.size.y, self.size.z, self.rule)?;
+
+        let cells: HashMap<IVec3, u8> = self.cells.iter().copied().collect();
+        let mut writer = RunWriter {
+            lines: vec![],
+            token: String::new(),
+            count: 0
+        };
+
+        // trailing dead cells, rows and layers are left out, only the separators before a live cell are written
+        let mut pending_layers = 0;
+
+        for z in 0..self.size.z {
+            let mut pending_rows = 0;
+
+            for y in 0..self.size.y {
+                let mut pending_dead = 0;
+
+                for x in 0..self.size.x {
+                    match cells.get(&ivec3(x, y, z)) {
+                        Some(value) if *value > 0 => {
+                            writer.push("/", pending_layers);
+                            writer.push("$", pending_rows);
+                            writer.push("b", pending_dead);
+                            writer.push(&self.state_token(*value), 1);
+
+                            pending_layers = 0;
+                            pending_rows = 0;
+                            pending_dead = 0;
+                        },
+                        _ => pending_dead += 1
+                    }
+                }
+
+                pending_rows += 1;
+            }
+
+            pending_layers += 1;
+        }
+
+        writer.push("!", 1);
+        writer.flush();
+
+        for line in writer.lines {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+// parse a header field 'key = value' as a size along one axis
+fn parse_size(field: &str) -> Result<(String, i32), PatternError> {
+    let (key, value) = field.split_once('=').ok_or_else(|| PatternError::InvalidHeader(field.trim().into()))?;
+    let value = value.trim().parse::<i32>().ok().filter(|value| *value >= 0)
+        .ok_or_else(|| PatternError::InvalidHeader(field.trim().into()))?;
+
+    Ok((key.trim().to_lowercase(), value))
+}
+
+impl FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        // header, the rule comes last as it contains commas of its own
+        let header = lines.next().ok_or(PatternError::MissingHeader)?;
+        let (sizes, rule) = header.split_once("rule").ok_or(PatternError::MissingHeader)?;
+        let rule = rule.trim().strip_prefix('=').ok_or_else(|| PatternError::InvalidHeader(format!("rule{}", rule)))?;
+        let rule = rule.parse::<Rule>().map_err(PatternError::InvalidRule)?;
+        let mut size = IVec3::ZERO;
+
+        for field in sizes.split(',').filter(|field| !field.trim().is_empty()) {
+            match parse_size(field)? {
+                (key, value) if key == "x" => size.x = value,
+                (key, value) if key == "y" => size.y = value,
+                (key, value) if key == "z" => size.z = value,
+                _ => return Err(PatternError::InvalidHeader(field.trim().into()))
+            }
+        }
+
+        // body
+        let mut cells = vec![];
+        let mut position = IVec3::ZERO;
+        let mut count: Option<usize> = None;
+        let mut prefix: Option<u8> = None;
+
+        'body: for line in lines {
+            for character in line.chars() {
+                let run = i32::try_from(count.unwrap_or(1)).map_err(|_| PatternError::RunTooLong)?;
+                let advance = |axis: i32| axis.checked_add(run).ok_or(PatternError::RunTooLong);
+
+                match character {
+                    '0'..='9' if prefix.is_none() => {
+                        let digit = character.to_digit(10).unwrap() as usize;
+
+                        count = Some(count.unwrap_or(0).checked_mul(10).and_then(|count| count.checked_add(digit))
+                            .ok_or(PatternError::RunTooLong)?);
+                        continue;
+                    },
+                    'p'..='y' if prefix.is_none() => {
+                        prefix = Some(character as u8 - b'p' + 1);
+                        continue;
+                    },
+                    'b' | '.' if prefix.is_none() => position.x = advance(position.x)?,
+                    '$' if prefix.is_none() => {
+                        position.x = 0;
+                        position.y = advance(position.y)?;
+                    },
+                    '/' if prefix.is_none() => {
+                        position.x = 0;
+                        position.y = 0;
+                        position.z = advance(position.z)?;
+                    },
+                    '!' if prefix.is_none() => {
+                        count = None;
+                        break 'body;
+                    },
+                    'o' | 'A'..='X' if character != 'o' || prefix.is_none() => {
+                        let value = match character {
+                            'o' => rule.states as u32,
+                            letter => prefix.take().unwrap_or(0) as u32 * LETTERS as u32 + (letter as u8 - b'A') as u32 + 1
+                        };
+
+                        if value > rule.states as u32 {
+                            return Err(PatternError::InvalidState(value));
+                        }
+
+                        let value = value as u8;
+
+                        for _ in 0..run {
+                            if position.cmpge(size).any() {
+                                return Err(PatternError::OutOfBounds(position));
+                            }
+
+                            cells.push((position, value));
+                            position.x += 1;
+                        }
+                    },
+                    character => return Err(PatternError::UnexpectedCharacter(character))
+                }
+
+                count = None;
+            }
+        }
+
+        if count.is_some() || prefix.is_some() {
+            return Err(PatternError::UnterminatedRun);
+        }
+
+        Ok(Pattern {
+            size,
+            rule,
+            cells
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sparse::Sparse;
+
+    fn glider() -> Pattern {
+        "#N glider\nx = 3, y = 3, z = 2, rule = 4-5/5/2/M\nbo$2o/o$b2o!\n".parse().unwrap()
+    }
+
+    fn sorted(mut cells: Vec<(IVec3, u8)>) -> Vec<(IVec3, u8)> {
+        cells.sort_by_key(|(position, value)| (position.to_array(), *value));
+        cells
+    }
+
+    #[test]
+    fn parses_runs_rows_and_layers() {
+        let pattern = glider();
+
+        assert_eq!(pattern.size, ivec3(3, 3, 2));
+        assert_eq!(sorted(pattern.cells), sorted(vec![
+            (ivec3(1, 0, 0), 2),
+            (ivec3(0, 1, 0), 2),
+            (ivec3(1, 1, 0), 2),
+            (ivec3(0, 0, 1), 2),
+            (ivec3(1, 1, 1), 2),
+            (ivec3(2, 1, 1), 2)
+        ]));
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let rule: Rule = "9-26/5-7,12-13,15/40/M".parse().unwrap();
+        let cells = vec![(ivec3(0, 0, 0), 40), (ivec3(4, 0, 0), 1), (ivec3(2, 3, 0), 25), (ivec3(1, 1, 5), 39), (ivec3(0, 2, 5), 40)];
+        let pattern = Pattern::from_cells(cells.into_iter(), &rule, None);
+        let parsed: Pattern = pattern.to_string().parse().unwrap();
+
+        assert_eq!(parsed.size, pattern.size);
+        assert_eq!(parsed.rule.to_string(), pattern.rule.to_string());
+        assert_eq!(sorted(parsed.cells), sorted(pattern.cells));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let header = "x = 3, y = 3, z = 2, rule = 4-5/5/2/M\n";
+
+        assert!(matches!("".parse::<Pattern>(), Err(PatternError::MissingHeader)));
+        assert!(matches!("x = 3, y = 3, z = 2\nbo!".parse::<Pattern>(), Err(PatternError::MissingHeader)));
+        assert!(matches!("x = -1, y = 3, z = 2, rule = 4-5/5/2/M\nbo!".parse::<Pattern>(), Err(PatternError::InvalidHeader(_))));
+        assert!(matches!("x = 3, y = 3, z = 2, rule = 4-5/5\nbo!".parse::<Pattern>(), Err(PatternError::InvalidRule(_))));
+        assert!(matches!(format!("{}bo?", header).parse::<Pattern>(), Err(PatternError::UnexpectedCharacter('?'))));
+        assert!(matches!(format!("{}4o!", header).parse::<Pattern>(), Err(PatternError::OutOfBounds(_))));
+        assert!(matches!(format!("{}bC!", header).parse::<Pattern>(), Err(PatternError::InvalidState(3))));
+    }
+
+    #[test]
+    fn rejects_unterminated_runs() {
+        let header = "x = 3, y = 3, z = 2, rule = 4-5/5/2/M\n";
+
+        for body in ["bo$2o/o$b2o3", "bo$2o/o$b2o\n12", "bo$2o/o$b2op", "o\nq"] {
+            assert!(matches!(format!("{}{}", header, body).parse::<Pattern>(), Err(PatternError::UnterminatedRun)), "{}", body);
+        }
+
+        // a pattern may end without '!', and '!' may carry a run count
+        assert_eq!(format!("{}bo$2o/o$b2o", header).parse::<Pattern>().unwrap().cells.len(), 6);
+        assert_eq!(format!("{}bo$2o/o$b2o3!", header).parse::<Pattern>().unwrap().cells.len(), 6);
+    }
+
+    #[test]
+    fn rejects_runs_that_overflow() {
+        let header = "x = 3, y = 3, z = 2, rule = 4-5/5/2/M\n";
+
+        for body in ["99999999999999999999999b!", "3000000000$o!", "2147483647b2147483647bo!", "2147483647/2147483647/o!"] {
+            assert!(matches!(format!("{}{}", header, body).parse::<Pattern>(), Err(PatternError::RunTooLong)), "{}", body);
+        }
+    }
+
+    #[test]
+    fn stamps_first_state_cells_as_seeds() {
+        let rule: Rule = "4-5/5/2/M".parse().unwrap();
+        let mut simulation = Sparse::new();
+
+        simulation.record_changes(true);
+
+        assert!(glider().stamp(&mut simulation, &rule, ivec3(10, 10, 10)));
+        assert_eq!(simulation.cell_count(), 6);
+        assert_eq!(simulation.take_changes().births.len(), 6);
+    }
+}
@@ -0,0 +1,194 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * February 4, 2023
+ */
+
+use bevy::prelude::{error, App, Input, KeyCode, Plugin, Res, ResMut};
+
+use cellular_automata::{
+    colour::Colour,
+    neighbours::NeighbourMethod,
+    rule::{ColourMethod, Rule, Value}
+};
+
+use crate::simulation::Simulations;
+
+// number keys in the order presets are selected by them
+const PRESET_KEYS: [KeyCode; 10] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Key0
+];
+
+pub struct Presets {
+    presets: Vec<(String, Rule)>
+}
+
+impl Presets {
+    // create new Presets holding the built-in rules
+    pub fn new() -> Presets {
+        let mut presets = Presets {
+            presets: vec![]
+        };
+
+        presets.add_preset("builder".into(), Rule {
+            survival_rule: Value::new(&[2, 6, 9]).unwrap(),
+            birth_rule: Value::new(&[4, 6, 8, 9, 10]).unwrap(),
+            states: 10,
+            colour_method: ColourMethod::DistanceToCenter(Colour::ORANGE_RED, Colour::RED),
+            neighbour_method: NeighbourMethod::Moore,
+            ..Default::default()
+        });
+
+        presets.add_preset("VonNeuman pyramid".into(), Rule {
+            survival_rule: Value::from_range(0..=6).unwrap(),
+            birth_rule: Value::new(&[1, 3]).unwrap(),
+            states: 2,
+            colour_method: ColourMethod::DistanceToCenter(Colour::LIME_GREEN, Colour::BLUE),
+            neighbour_method: NeighbourMethod::VonNeuman,
+            ..Default::default()
+        });
+
+        presets.add_preset("fancy pattern".into(), Rule {
+            survival_rule: Value::new(&[0, 1, 2, 3, 7, 8, 9, 11, 13, 18, 21, 22, 24, 26]).unwrap(),
+            birth_rule: Value::new(&[4, 13, 17, 20, 21, 22, 23, 24, 26]).unwrap(),
+            states: 4,
+            colour_method: ColourMethod::StateLerp(Colour::RED, Colour::BLUE),
+            neighbour_method: NeighbourMethod::Moore,
+            ..Default::default()
+        });
+
+        presets.add_preset("crystals".into(), Rule {
+            survival_rule: Value::new(&[5, 6, 7, 8]).unwrap(),
+            birth_rule: Value::new(&[6, 7, 9]).unwrap(),
+            states: 10,
+            colour_method: ColourMethod::DistanceToCenter(Colour::GOLD, Colour::BLUE),
+            neighbour_method: NeighbourMethod::Moore,
+            ..Default::default()
+        });
+
+        presets.add_preset("swapping structures".into(), Rule {
+            survival_rule: Value::new(&[3, 6, 9]).unwrap(),
+            birth_rule: Value::new(&[4, 8, 10]).unwrap(),
+            states: 20,
+            colour_method: ColourMethod::StateLerp(Colour::RED, Colour::GREEN),
+            neighbour_method: NeighbourMethod::Moore,
+            ..Default::default()
+        });
+
+        presets.add_preset("slowly expanding blob".into(), Rule {
+            survival_rule: Value::from_range(9..=26).unwrap(),
+            birth_rule: Value::new(&[5, 6, 7, 12, 13, 15]).unwrap(),
+            states: 20,
+            colour_method: ColourMethod::StateLerp(Colour::BLUE, Colour::RED),
+            neighbour_method: NeighbourMethod::Moore,
+            ..Default::default()
+        });
+
+        presets.add_preset("445 rule".into(), Rule {
+            survival_rule: Value::new(&[4]).unwrap(),
+            birth_rule: Value::new(&[4]).unwrap(),
+            states: 5,
+            colour_method: ColourMethod::StateLerp(Colour::BLACK, Colour::RED),
+            neighbour_method: NeighbourMethod::Moore,
+            ..Default::default()
+        });
+
+        presets.add_preset("expand, then die".into(), Rule {
+            survival_rule: Value::new(&[4]).unwrap(),
+            birth_rule: Value::new(&[3]).unwrap(),
+            states: 20,
+            colour_method: ColourMethod::StateLerp(Colour::BLACK, Colour::RED),
+            neighbour_method: NeighbourMethod::Moore,
+            ..Default::default()
+        });
+
+        presets.add_preset("???".into(), Rule {
+            survival_rule: Value::new(&[6, 7]).unwrap(),
+            birth_rule: Value::new(&[4, 6, 9, 10, 11]).unwrap(),
+            states: 6,
+            colour_method: ColourMethod::StateLerp(Colour::BLUE, Colour::RED),
+            neighbour_method: NeighbourMethod::Moore,
+            ..Default::default()
+        });
+
+        presets.add_preset("large lines".into(), Rule {
+            survival_rule: Value::new(&[5]).unwrap(),
+            birth_rule: Value::new(&[4, 6, 9, 10, 11, 16, 17, 18, 19, 20, 21, 22, 23, 24]).unwrap(),
+            states: 35,
+            colour_method: ColourMethod::StateLerp(Colour::BLUE, Colour::RED),
+            neighbour_method: NeighbourMethod::Moore,
+            ..Default::default()
+        });
+
+        presets
+    }
+
+    pub fn add_preset(&mut self, name: String, rule: Rule) {
+        self.presets.push((name, rule));
+    }
+
+    // get names of all presets in selection order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.presets.iter().map(|(name, _)| name.as_str())
+    }
+
+    // find preset by name, ignoring case, and return its rule
+    pub fn select(&self, name: &str) -> Option<Rule> {
+        self.presets.iter().find(|(preset_name, _)| preset_name.eq_ignore_ascii_case(name)).map(|(_, rule)| rule.clone())
+    }
+}
+
+// swap the rule for a preset when user presses a number key
+pub fn select_preset(input: Res<Input<KeyCode>>, presets: Res<Presets>, mut rule: ResMut<Rule>,
+                     mut simulations: ResMut<Simulations>) {
+    let index = match PRESET_KEYS.iter().position(|key| input.just_pressed(*key)) {
+        Some(index) if index < presets.presets.len() => index,
+        _ => return
+    };
+
+    let (name, preset) = &presets.presets[index];
+
+    if let Err(error) = simulations.check_rule(preset) {
+        error!("cannot use preset '{}': {}", name, error);
+        return;
+    }
+
+    *rule = preset.clone();
+    simulations.reset(&rule);
+}
+
+pub struct PresetsPlugin;
+
+impl Plugin for PresetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(select_preset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_round_trip_through_rule_notation() {
+        for (name, rule) in &Presets::new().presets {
+            let parsed = rule.to_string().parse::<Rule>().unwrap();
+
+            assert_eq!(parsed.to_string(), rule.to_string(), "{}", name);
+            assert!((0..=26).all(|count| parsed.survival_rule.in_range(count) == rule.survival_rule.in_range(count)), "{}", name);
+            assert!((0..=26).all(|count| parsed.birth_rule.in_range(count) == rule.birth_rule.in_range(count)), "{}", name);
+            assert_eq!(parsed.states, rule.states, "{}", name);
+            assert_eq!(parsed.neighbour_method.neighbour_count(), rule.neighbour_method.neighbour_count(), "{}", name);
+            assert_eq!(rule.validate(), Ok(()), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,489 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * March 18, 2023
+ */
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path
+};
+
+use bevy_math::{ivec3, IVec3};
+
+use crate::{
+    engine::{Changes, Simulation},
+    rule::Rule,
+    snapshot::{read_framed, write_framed, ByteReader, Snapshot, SnapshotError}
+};
+
+const MAGIC: &[u8; 8] = b"CA3DRECD";
+pub const VERSION: u32 = 1;
+
+// generations between the full states a replay keeps, bounding how many frames a seek has to apply
+const KEYFRAME_INTERVAL: u64 = 64;
+
+// whole run as its first generation followed by the births + deaths of every step ; the snapshot holds the rule,
+// bounds and seed so a recording describes itself
+pub struct Recording {
+    pub start: Snapshot,
+    // births + deaths taking generation start + n to start + n + 1, relative to the center of the world
+    pub frames: Vec<Changes>,
+    center: IVec3
+}
+
+// append signed value as zigzag encoded variable length integer
+fn write_varint(bytes: &mut Vec<u8>, value: i32) {
+    let mut value = ((value << 1) ^ (value >> 31)) as u32;
+
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    bytes.push(value as u8);
+}
+
+fn read_varint(reader: &mut ByteReader) -> Result<i32, SnapshotError> {
+    let mut value = 0u32;
+
+    for shift in (0..35).step_by(7) {
+        let byte = reader.u8()?;
+
+        value |= ((byte & 0x7f) as u32) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok((value >> 1) as i32 ^ -((value & 1) as i32));
+        }
+    }
+
+    Err(SnapshotError::InvalidVarint)
+}
+
+// append sorted positions as differences from the previous one, which are small for clustered cells
+fn write_positions(bytes: &mut Vec<u8>, positions: &[IVec3]) {
+    let mut positions = positions.to_vec();
+    let mut previous = IVec3::ZERO;
+
+    positions.sort_by_key(|position| position.to_array());
+    bytes.extend((positions.len() as u32).to_le_bytes());
+
+    for position in positions {
+        for axis in (position - previous).to_array() {
+            write_varint(bytes, axis);
+        }
+
+        previous = position;
+    }
+}
+
+fn read_positions(reader: &mut ByteReader) -> Result<Vec<IVec3>, SnapshotError> {
+    let count = reader.u32()? as usize;
+
+    // every position takes at least 3 bytes, check before allocating so a bad count cannot exhaust memory
+    if count > reader.remaining() / 3 {
+        return Err(SnapshotError::Truncated);
+    }
+
+    let mut positions = Vec::with_capacity(count);
+    let mut previous = IVec3::ZERO;
+
+    for _ in 0..count {
+        previous += ivec3(read_varint(reader)?, read_varint(reader)?, read_varint(reader)?);
+        positions.push(previous);
+    }
+
+    Ok(positions)
+}
+
+impl Recording {
+    // start recording a simulation from its current generation
+    pub fn start(simulation: &dyn Simulation, rule: &Rule, seed: u64, rng_position: u128) -> Recording {
+        Recording {
+            start: simulation.snapshot(rule, seed, rng_position),
+            frames: vec![],
+            center: simulation.center()
+        }
+    }
+
+    // add births + deaths of the next step, as xyz positions of the simulation being recorded
+    pub fn push(&mut self, mut changes: Changes) {
+        for position in changes.births.iter_mut().chain(changes.deaths.iter_mut()) {
+            *position -= self.center;
+        }
+
+        self.frames.push(changes);
+    }
+
+    pub fn first_generation(&self) -> u64 {
+        self.start.generation
+    }
+
+    pub fn last_generation(&self) -> u64 {
+        self.start.generation + self.frames.len() as u64
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), SnapshotError> {
+        let mut payload = vec![];
+
+        self.start.write_payload(&mut payload);
+        payload.extend((self.frames.len() as u64).to_le_bytes());
+
+        for frame in &self.frames {
+            write_positions(&mut payload, &frame.births);
+            write_positions(&mut payload, &frame.deaths);
+        }
+
+        Ok(write_framed(writer, MAGIC, VERSION, &payload)?)
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Recording, SnapshotError> {
+        let payload = read_framed(reader, MAGIC, VERSION)?;
+        let mut reader = ByteReader::new(&payload);
+        let start = Snapshot::read_payload(&mut reader)?;
+        let count = reader.u64()? as usize;
+
+        // every frame takes at least 8 bytes
+        if count > reader.remaining() / 8 {
+            return Err(SnapshotError::Truncated);
+        }
+
+        let mut frames = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            frames.push(Changes {
+                births: read_positions(&mut reader)?,
+                deaths: read_positions(&mut reader)?
+            });
+        }
+
+        Ok(Recording {
+            start,
+            frames,
+            center: IVec3::ZERO
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        self.write(&mut BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Recording, SnapshotError> {
+        Recording::read(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+// apply one frame : decaying cells lose a state, then births enter the first state and deaths leave it
+pub fn apply_changes(cells: &mut HashMap<IVec3, u8>, changes: &Changes, states: u8) {
+    cells.retain(|_, value| {
+        if *value < states {
+            *value -= 1;
+        }
+
+        *value > 0
+    });
+
+    for position in &changes.births {
+        cells.insert(*position, states);
+    }
+
+    for position in &changes.deaths {
+        if states > 1 {
+            cells.insert(*position, states - 1);
+        } else {
+            cells.remove(position);
+        }
+    }
+}
+
+// plays a recording back at any generation without simulating the rule
+pub struct Replay {
+    recording: Recording,
+    keyframes: Vec<HashMap<IVec3, u8>>,
+    cells: HashMap<IVec3, u8>,
+    generation: u64,
+    // positions that changed since the replay was last shown, None until it was shown once
+    changed: Option<HashSet<IVec3>>
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Replay {
+        let states = recording.start.rule.states;
+        let mut cells: HashMap<IVec3, u8> = recording.start.cells.iter().copied().collect();
+        let mut keyframes = vec![cells.clone()];
+
+        for (index, frame) in recording.frames.iter().enumerate() {
+            apply_changes(&mut cells, frame, states);
+
+            if (index as u64 + 1).is_multiple_of(KEYFRAME_INTERVAL) {
+                keyframes.push(cells.clone());
+            }
+        }
+
+        Replay {
+            cells: keyframes[0].clone(),
+            generation: recording.first_generation(),
+            recording,
+            keyframes,
+            changed: None
+        }
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn rule(&self) -> &Rule {
+        &self.recording.start.rule
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // move to generation, clamped to the recorded range
+    pub fn seek(&mut self, generation: u64) {
+        let generation = generation.clamp(self.recording.first_generation(), self.recording.last_generation());
+        let frame = (generation - self.recording.first_generation()) as usize;
+        let current = (self.generation - self.recording.first_generation()) as usize;
+        let states = self.recording.start.rule.states;
+
+        // jump to the closest keyframe unless playing forward from here is shorter
+        let keyframe = frame / KEYFRAME_INTERVAL as usize;
+        let mut applied = current;
+
+        if frame < current || frame - current > frame - keyframe * KEYFRAME_INTERVAL as usize {
+            let cells = self.keyframes[keyframe].clone();
+
+            if let Some(changed) = &mut self.changed {
+                changed.extend(self.cells.keys().chain(cells.keys()));
+            }
+
+            self.cells = cells;
+            applied = keyframe * KEYFRAME_INTERVAL as usize;
+        }
+
+        for changes in &self.recording.frames[applied..frame] {
+            // decaying cells change without showing up in the frame
+            if let Some(changed) = &mut self.changed {
+                changed.extend(self.cells.iter().filter(|(_, value)| **value < states).map(|(position, _)| *position));
+                changed.extend(changes.births.iter().chain(&changes.deaths));
+            }
+
+            apply_changes(&mut self.cells, changes, states);
+        }
+
+        self.generation = generation;
+    }
+
+    // xyz position relative to the center of the world and value of every live cell
+    pub fn cells(&self) -> impl Iterator<Item = (IVec3, u8)> + '_ {
+        self.cells.iter().map(|(position, value)| (*position, *value))
+    }
+
+    // show the current generation in a simulation ; once shown, only the cells changed since are set again, so the
+    // simulation must not be changed in between
+    pub fn show(&mut self, simulation: &mut dyn Simulation) {
+        let center = simulation.center();
+
+        match self.changed.replace(HashSet::new()) {
+            Some(changed) => {
+                let cells = changed.into_iter()
+                    .map(|position| (position + center, self.cells.get(&position).copied().unwrap_or(0)))
+                    .collect::<Vec<_>>();
+
+                simulation.set_cells(&self.recording.start.rule, &cells, self.generation);
+            },
+            None => {
+                simulation.set_bounds(self.recording.start.bounds);
+
+                let center = simulation.center();
+                let cells = self.cells().map(|(position, value)| (position + center, value)).collect::<Vec<_>>();
+
+                simulation.restore(&self.recording.start.rule, &cells, self.generation);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        multi_threading::MultiThreaded,
+        rule::Value,
+        seeding::SeedRng,
+        sparse::Sparse
+    };
+
+    const BOUNDS: i32 = 24;
+    // long enough to pass two keyframes
+    const GENERATIONS: usize = 150;
+
+    fn rule() -> Rule {
+        Rule {
+            survival_rule: Value::from_range(9..=26).unwrap(),
+            birth_rule: Value::new(&[5, 6, 7, 12, 13, 15]).unwrap(),
+            states: 5,
+            bounding_size: IVec3::splat(BOUNDS),
+            ..Default::default()
+        }
+    }
+
+    fn sorted(cells: impl Iterator<Item = (IVec3, u8)>) -> Vec<(IVec3, u8)> {
+        let mut cells = cells.collect::<Vec<_>>();
+
+        cells.sort_by_key(|(position, _)| position.to_array());
+
+        cells
+    }
+
+    // record a seeded run, returning the recording and the cells of every generation relative to the center
+    fn record() -> (Recording, Vec<Vec<(IVec3, u8)>>) {
+        let rule = rule();
+        let mut simulation = MultiThreaded::new();
+        let mut rng = SeedRng::seed_from_u64(7);
+        let positions = (0..BOUNDS * BOUNDS * BOUNDS / 4)
+            .map(|_| IVec3::new(rng.gen_range(0..BOUNDS), rng.gen_range(0..BOUNDS), rng.gen_range(0..BOUNDS)))
+            .collect::<Vec<_>>();
+
+        simulation.set_bounds(rule.bounding_size);
+        simulation.seed(&rule, &positions);
+        simulation.record_changes(true);
+
+        let center = simulation.center();
+        let generation_cells = |simulation: &MultiThreaded| {
+            sorted(simulation.cells().map(|(position, cell)| (position - center, cell.value)))
+        };
+        let mut recording = Recording::start(&simulation, &rule, 7, 0);
+        let mut generations = vec![generation_cells(&simulation)];
+
+        for _ in 0..GENERATIONS {
+            simulation.step(&rule);
+            recording.push(simulation.take_changes());
+            generations.push(generation_cells(&simulation));
+        }
+
+        assert!(!generations[GENERATIONS].is_empty(), "run died out before the last generation");
+
+        (recording, generations)
+    }
+
+    fn bytes(recording: &Recording) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        recording.write(&mut bytes).unwrap();
+
+        bytes
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let (recording, _) = record();
+        let read = Recording::read(&mut &bytes(&recording)[..]).unwrap();
+
+        assert_eq!(read.start.cells, recording.start.cells);
+        assert_eq!(read.first_generation(), recording.first_generation());
+        assert_eq!(read.last_generation(), recording.last_generation());
+
+        // positions are written sorted
+        let positions = |positions: &[IVec3]| sorted(positions.iter().map(|position| (*position, 0)));
+
+        for (read, expected) in read.frames.iter().zip(&recording.frames) {
+            assert_eq!(positions(&read.births), positions(&expected.births));
+            assert_eq!(positions(&read.deaths), positions(&expected.deaths));
+        }
+    }
+
+    #[test]
+    fn seek_matches_simulation() {
+        let (recording, generations) = record();
+        let mut replay = Replay::new(Recording::read(&mut &bytes(&recording)[..]).unwrap());
+
+        // forward steps, jumps across keyframes and backward seeks
+        for generation in (0..=GENERATIONS).chain([GENERATIONS / 2, 3, 130, 64, 63, 129, 0, GENERATIONS]) {
+            replay.seek(generation as u64);
+
+            assert_eq!(replay.generation(), generation as u64);
+            assert_eq!(sorted(replay.cells()), generations[generation], "generation {} differs", generation);
+        }
+
+        replay.seek(GENERATIONS as u64 + 10);
+
+        assert_eq!(replay.generation(), GENERATIONS as u64);
+    }
+
+    #[test]
+    fn show_updates_simulations_in_place() {
+        let (recording, generations) = record();
+        let mut replay = Replay::new(recording);
+        let engines = || -> [Box<dyn Simulation>; 2] { [Box::new(MultiThreaded::new()), Box::new(Sparse::new())] };
+        let state = |simulation: &dyn Simulation| {
+            let center = simulation.center();
+
+            sorted(simulation.cells().map(|(position, cell)| (position - center, cell.value)))
+        };
+        let neighbours = |simulation: &dyn Simulation| {
+            let center = simulation.center();
+
+            sorted(simulation.cells().map(|(position, cell)| (position - center, cell.neighbours)))
+        };
+
+        for (mut simulation, mut restored) in engines().into_iter().zip(engines()) {
+            replay.changed = None;
+            replay.seek(0);
+            replay.show(simulation.as_mut());
+
+            for generation in [1, 2, 3, 70, 71, GENERATIONS, 64, 63, 5, 0, 129, 130] {
+                replay.seek(generation as u64);
+                replay.show(simulation.as_mut());
+
+                assert_eq!(simulation.generation(), generation as u64);
+                assert_eq!(state(simulation.as_ref()), generations[generation], "generation {} differs", generation);
+
+                // neighbour counts match the same generation restored from scratch
+                restored.set_bounds(replay.recording.start.bounds);
+
+                let center = restored.center();
+                let cells = replay.cells().map(|(position, value)| (position + center, value)).collect::<Vec<_>>();
+
+                restored.restore(replay.rule(), &cells, generation as u64);
+
+                assert_eq!(neighbours(simulation.as_ref()), neighbours(restored.as_ref()), "neighbours of generation {} differ", generation);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_damaged_files() {
+        let (recording, _) = record();
+        let bytes = bytes(&recording);
+
+        assert!(matches!(Recording::read(&mut &bytes[..bytes.len() - 1]), Err(SnapshotError::Truncated)));
+        assert!(matches!(Recording::read(&mut &bytes[..bytes.len() / 2]), Err(SnapshotError::Truncated)));
+
+        let mut damaged = bytes.clone();
+        let payload = damaged.len() - 20;
+
+        damaged[payload] ^= 0xff;
+
+        assert!(matches!(Recording::read(&mut &damaged[..]), Err(SnapshotError::ChecksumMismatch)));
+
+        // a snapshot is not a recording
+        let mut snapshot = vec![];
+
+        recording.start.write(&mut snapshot).unwrap();
+
+        assert!(matches!(Recording::read(&mut &snapshot[..]), Err(SnapshotError::UnknownFormat)));
+
+        let mut other_version = bytes;
+
+        other_version[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+        assert!(matches!(Recording::read(&mut &other_version[..]), Err(SnapshotError::UnsupportedVersion(_))));
+    }
+}
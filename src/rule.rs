@@ -4,56 +4,186 @@
  * January 20, 2023
  */
 
-use std::ops::RangeInclusive;
+use std::{
+    fmt,
+    ops::RangeInclusive,
+    str::FromStr
+};
+
+use bevy_math::IVec3;
 
-use bevy::prelude::Color;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    boundary::Boundaries,
+    colour::Colour,
+    generators::GeneratorError,
     neighbours::NeighbourMethod,
+    seeding::Seeding,
     utils
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
 pub struct Value ([bool; 27]);
 
-#[allow(dead_code)]
 impl Value {
-    pub fn new(indices: &[u8]) -> Self {
+    // set of neighbour counts, failing if one is above 26
+    pub fn new(indices: &[u8]) -> Result<Self, RuleParseError> {
         let mut result = Value([false; 27]);
 
         for index in indices {
-            result.0[*index as usize] = true;
+            *result.0.get_mut(*index as usize).ok_or_else(|| RuleParseError::IndexOutOfRange(index.to_string()))? = true;
         }
 
-        result
+        Ok(result)
     }
 
-    pub fn from_range(indices: RangeInclusive<u8>) -> Self {
+    pub fn from_range(indices: RangeInclusive<u8>) -> Result<Self, RuleParseError> {
+        Value::new(&indices.collect::<Vec<_>>())
+    }
+
+    pub fn in_range(&self, value: u8) -> bool {
+        self.0[value as usize]
+    }
+
+    // get highest neighbour count in the set, if any
+    pub fn max(&self) -> Option<u8> {
+        self.0.iter().rposition(|in_range| *in_range).map(|index| index as u8)
+    }
+}
+
+// format as comma separated indices and ranges, e.g. '5-7,12-13,15'
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        let mut index = 0;
+
+        while index < self.0.len() {
+            if !self.0[index] {
+                index += 1;
+                continue;
+            }
+
+            let start = index;
+
+            while index + 1 < self.0.len() && self.0[index + 1] {
+                index += 1;
+            }
+
+            if !first {
+                write!(f, ",")?;
+            }
+
+            if start == index {
+                write!(f, "{}", start)?;
+            } else {
+                write!(f, "{}-{}", start, index)?;
+            }
+
+            first = false;
+            index += 1;
+        }
+
+        Ok(())
+    }
+}
+
+// parse comma separated indices and ranges ; an empty string is an empty set
+impl FromStr for Value {
+    type Err = RuleParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut result = Value([false; 27]);
 
-        for index in indices {
-            result.0[index as usize] = true;
+        for token in s.split(',').map(str::trim).filter(|token| !token.is_empty()) {
+            let (start, end) = match token.split_once('-') {
+                Some((start, end)) => (parse_index(start, token)?, parse_index(end, token)?),
+                None => {
+                    let index = parse_index(token, token)?;
+
+                    (index, index)
+                }
+            };
+
+            if start > end {
+                return Err(RuleParseError::InvalidRange(token.into()));
+            }
+
+            for index in start..=end {
+                result.0[index as usize] = true;
+            }
         }
 
-        result
+        Ok(result)
     }
+}
 
-    pub fn in_range(&self, value: u8) -> bool {
-        self.0[value as usize]
+// used to (de)serialise Value in rule notation
+impl TryFrom<String> for Value {
+    type Error = RuleParseError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Value> for String {
+    fn from(value: Value) -> Self {
+        value.to_string()
+    }
+}
+
+// parse a single neighbour count belonging to token
+fn parse_index(s: &str, token: &str) -> Result<u8, RuleParseError> {
+    let index = s.trim().parse::<u8>().map_err(|_| RuleParseError::InvalidNumber(token.into()))?;
+
+    if index as usize >= 27 {
+        return Err(RuleParseError::IndexOutOfRange(token.into()));
+    }
+
+    Ok(index)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuleParseError {
+    WrongFieldCount(usize),
+    InvalidNumber(String),
+    InvalidRange(String),
+    IndexOutOfRange(String),
+    InvalidStates(String),
+    InvalidNeighbourMethod(String)
+}
+
+impl fmt::Display for RuleParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleParseError::WrongFieldCount(count) => {
+                write!(f, "expected 4 fields 'survival/birth/states/neighbours', found {}", count)
+            },
+            RuleParseError::InvalidNumber(token) => write!(f, "'{}' is not a valid neighbour count", token),
+            RuleParseError::InvalidRange(token) => write!(f, "'{}' is not a valid range", token),
+            RuleParseError::IndexOutOfRange(token) => write!(f, "'{}' is outside the neighbour range 0-26", token),
+            RuleParseError::InvalidStates(token) => write!(f, "'{}' is not a valid number of states", token),
+            RuleParseError::InvalidNeighbourMethod(token) => {
+                write!(f, "'{}' is not a valid neighbour method, expected 'M' or 'N'", token)
+            }
+        }
     }
 }
 
-#[allow(dead_code)]
-#[derive(Clone)]
+impl std::error::Error for RuleParseError {}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub enum ColourMethod {
-    Single(Color),
-    StateLerp(Color, Color),
-    DistanceToCenter(Color, Color),
-    Neighbour(Color, Color)
+    Single(Colour),
+    StateLerp(Colour, Colour),
+    DistanceToCenter(Colour, Colour),
+    Neighbour(Colour, Colour)
 }
 
 impl ColourMethod {
-    pub fn colour(&self, states: u8, state: u8, neighbours: u8, distance_to_center: f32) -> Color {
+    pub fn colour(&self, states: u8, state: u8, neighbours: u8, distance_to_center: f32) -> Colour {
         match self {
             ColourMethod::Single(c) => *c,
             ColourMethod::StateLerp(c1, c2) => {
@@ -73,12 +203,235 @@ impl ColourMethod {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Rule {
     pub survival_rule: Value,
     pub birth_rule: Value,
     pub states: u8,
-    pub bounding_size: i32,
+    pub bounding_size: IVec3,
     pub colour_method: ColourMethod,
-    pub neighbour_method: NeighbourMethod
+    pub neighbour_method: NeighbourMethod,
+    pub boundary: Boundaries,
+    pub seeding: Seeding
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuleError {
+    NoStates,
+    InvalidBoundingSize(IVec3),
+    SurvivalOutOfRange(u8, u8),
+    BirthOutOfRange(u8, u8),
+    InvalidGenerator(GeneratorError),
+    BirthWithoutNeighbours
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleError::NoStates => write!(f, "states must be at least 1"),
+            RuleError::InvalidBoundingSize(size) => write!(f, "bounding size must be greater than 0 on every axis, found {}", size),
+            RuleError::SurvivalOutOfRange(count, max) => {
+                write!(f, "survival neighbour count {} can never occur, the neighbour method has {} neighbours", count, max)
+            },
+            RuleError::BirthOutOfRange(count, max) => {
+                write!(f, "birth neighbour count {} can never occur, the neighbour method has {} neighbours", count, max)
+            },
+            RuleError::InvalidGenerator(error) => write!(f, "{}", error),
+            RuleError::BirthWithoutNeighbours => {
+                write!(f, "birth on 0 neighbours needs a bounded world, every empty cell of an unbounded one would be born")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+impl Rule {
+    // check rule can be simulated
+    pub fn validate(&self) -> Result<(), RuleError> {
+        if self.states == 0 {
+            return Err(RuleError::NoStates);
+        }
+
+        if self.bounding_size.cmple(IVec3::ZERO).any() {
+            return Err(RuleError::InvalidBoundingSize(self.bounding_size));
+        }
+
+        let max = self.neighbour_method.neighbour_count();
+
+        if let Some(count) = self.survival_rule.max().filter(|count| *count > max) {
+            return Err(RuleError::SurvivalOutOfRange(count, max));
+        }
+
+        if let Some(count) = self.birth_rule.max().filter(|count| *count > max) {
+            return Err(RuleError::BirthOutOfRange(count, max));
+        }
+
+        self.seeding.generator.validate().map_err(RuleError::InvalidGenerator)
+    }
+}
+
+// implement Default trait for Rule, the 'slowly expanding blob' rule
+impl Default for Rule {
+    fn default() -> Self {
+        Rule {
+            survival_rule: Value::from_range(9..=26).unwrap(),
+            birth_rule: Value::new(&[5, 6, 7, 12, 13, 15]).unwrap(),
+            states: 20,
+            bounding_size: IVec3::splat(50),
+            colour_method: ColourMethod::StateLerp(Colour::BLUE, Colour::RED),
+            neighbour_method: NeighbourMethod::Moore,
+            boundary: Boundaries::default(),
+            seeding: Seeding::default()
+        }
+    }
+}
+
+// format as 'survival/birth/states/neighbours', e.g. '9-26/5-7,12-13,15/20/M'
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let neighbour_method = match self.neighbour_method {
+            NeighbourMethod::Moore => "M",
+            NeighbourMethod::VonNeuman => "N"
+        };
+
+        write!(f, "{}/{}/{}/{}", self.survival_rule, self.birth_rule, self.states, neighbour_method)
+    }
+}
+
+// parse 'survival/birth/states/neighbours' ; bounds and colour method are taken from the default rule
+impl FromStr for Rule {
+    type Err = RuleParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.trim().split('/').collect::<Vec<_>>();
+
+        if fields.len() != 4 {
+            return Err(RuleParseError::WrongFieldCount(fields.len()));
+        }
+
+        let survival_rule = fields[0].parse()?;
+        let birth_rule = fields[1].parse()?;
+        let states = fields[2].trim().parse::<u8>().map_err(|_| RuleParseError::InvalidStates(fields[2].into()))?;
+        let neighbour_method = match fields[3].trim() {
+            "M" | "m" => NeighbourMethod::Moore,
+            "N" | "n" => NeighbourMethod::VonNeuman,
+            token => return Err(RuleParseError::InvalidNeighbourMethod(token.into()))
+        };
+
+        Ok(Rule {
+            survival_rule,
+            birth_rule,
+            states,
+            neighbour_method,
+            ..Rule::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generators::Generator;
+
+    #[test]
+    fn formats_and_parses_rule_notation() {
+        for notation in ["9-26/5-7,12-13,15/20/M", "0-6/1,3/2/N", "/4/5/M", "4/4/5/M", "0-26/0-26/255/M"] {
+            let rule = notation.parse::<Rule>().unwrap();
+
+            assert_eq!(rule.to_string(), notation);
+            assert_eq!(rule.to_string().parse::<Rule>().unwrap().to_string(), notation);
+        }
+
+        let rule = " 1, 3-4 ,5 / 2 / 3 / n ".parse::<Rule>().unwrap();
+
+        assert_eq!(rule.to_string(), "1,3-5/2/3/N");
+        assert!(rule.survival_rule.in_range(4) && !rule.survival_rule.in_range(2));
+    }
+
+    #[test]
+    fn reports_the_token_at_fault() {
+        assert_eq!("4/4/5".parse::<Rule>().err(), Some(RuleParseError::WrongFieldCount(3)));
+        assert_eq!("4/4/5/M/1".parse::<Rule>().err(), Some(RuleParseError::WrongFieldCount(5)));
+        assert_eq!("7-5/4/5/M".parse::<Rule>().err(), Some(RuleParseError::InvalidRange("7-5".into())));
+        assert_eq!("4/a,6/5/M".parse::<Rule>().err(), Some(RuleParseError::InvalidNumber("a".into())));
+        assert_eq!("4/1-x/5/M".parse::<Rule>().err(), Some(RuleParseError::InvalidNumber("1-x".into())));
+        assert_eq!("4/27/5/M".parse::<Rule>().err(), Some(RuleParseError::IndexOutOfRange("27".into())));
+        assert_eq!("4/4/256/M".parse::<Rule>().err(), Some(RuleParseError::InvalidStates("256".into())));
+        assert_eq!("4/4/five/M".parse::<Rule>().err(), Some(RuleParseError::InvalidStates("five".into())));
+        assert_eq!("4/4/5/X".parse::<Rule>().err(), Some(RuleParseError::InvalidNeighbourMethod("X".into())));
+    }
+
+    #[test]
+    fn rejects_neighbour_counts_above_26() {
+        assert_eq!(Value::new(&[4, 27]).err(), Some(RuleParseError::IndexOutOfRange("27".into())));
+        assert!(Value::from_range(20..=30).is_err());
+        assert_eq!(Value::new(&[0, 26]).unwrap().max(), Some(26));
+    }
+
+    #[test]
+    fn rejects_out_of_range_values_when_deserialising() {
+        let rule = ron::to_string(&Rule::default()).unwrap().replace("\"9-26\"", "\"9-30\"");
+
+        assert!(ron::from_str::<Rule>(&rule).is_err());
+        assert!(ron::from_str::<Value>("\"5-7,12-13,15\"").is_ok());
+        assert!(ron::from_str::<Value>("\"255\"").is_err());
+    }
+
+    #[test]
+    fn validates_rules() {
+        assert_eq!(Rule::default().validate(), Ok(()));
+
+        let rule = Rule {
+            states: 0,
+            ..Default::default()
+        };
+
+        assert_eq!(rule.validate(), Err(RuleError::NoStates));
+
+        for bounding_size in [IVec3::new(0, 50, 50), IVec3::new(50, -1, 50), IVec3::new(50, 50, 0)] {
+            let rule = Rule {
+                bounding_size,
+                ..Default::default()
+            };
+
+            assert_eq!(rule.validate(), Err(RuleError::InvalidBoundingSize(bounding_size)));
+        }
+
+        let rule = "1-6/1,7/2/N".parse::<Rule>().unwrap();
+
+        assert_eq!(rule.validate(), Err(RuleError::BirthOutOfRange(7, 6)));
+
+        let rule = "1-7/1/2/N".parse::<Rule>().unwrap();
+
+        assert_eq!(rule.validate(), Err(RuleError::SurvivalOutOfRange(7, 6)));
+    }
+
+    #[test]
+    fn validates_the_generator() {
+        let generators = [
+            (Generator::Noise {radius: -1, amount: 10}, GeneratorError::InvalidRadius(-1)),
+            (Generator::Noise {radius: 4, amount: usize::MAX}, GeneratorError::InvalidAmount(usize::MAX)),
+            (Generator::Sphere {radius: 4, density: 1.5}, GeneratorError::InvalidDensity(1.5)),
+            (Generator::Shell {radius: 4, thickness: 5, density: 0.5}, GeneratorError::InvalidThickness(5)),
+            (Generator::Gaussian {sigma: -2.0, amount: 10}, GeneratorError::InvalidSigma(-2.0))
+        ];
+
+        for (generator, error) in generators {
+            let mut rule = Rule::default();
+
+            rule.seeding.generator = generator;
+
+            assert_eq!(rule.validate(), Err(RuleError::InvalidGenerator(error)));
+        }
+
+        for generator in Generator::all() {
+            let mut rule = Rule::default();
+
+            rule.seeding.generator = generator;
+
+            assert_eq!(rule.validate(), Ok(()), "{}", generator.name());
+        }
+    }
 }
@@ -0,0 +1,86 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * February 6, 2023
+ */
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture
+};
+
+use cellular_automata::rule::Rule;
+
+use crate::simulation::Simulations;
+
+// rule loaded from a RON file in the assets folder, e.g. 'rules/default.rule'
+#[derive(TypeUuid)]
+#[uuid = "5f4c3a0e-9d1b-4b6e-8a27-3c9e1f0d7b42"]
+pub struct RuleAsset(pub Rule);
+
+#[derive(Default)]
+pub struct RuleAssetLoader;
+
+impl AssetLoader for RuleAssetLoader {
+    fn load<'a>(&'a self, bytes: &'a [u8], load_context: &'a mut LoadContext) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let rule = ron::de::from_bytes::<Rule>(bytes)?;
+
+            load_context.set_default_asset(LoadedAsset::new(RuleAsset(rule)));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rule"]
+    }
+}
+
+// path of the rule file relative to the assets folder ; only present when a rule file is used
+pub struct RuleFilePath(pub String);
+
+// keeps the rule file loaded so it is reloaded when saved
+pub struct RuleFileHandle(pub Handle<RuleAsset>);
+
+fn load_rule_file(mut commands: Commands, path: Option<Res<RuleFilePath>>, asset_server: Res<AssetServer>) {
+    if let Some(path) = path {
+        asset_server.watch_for_changes().unwrap();
+
+        commands.insert_resource(RuleFileHandle(asset_server.load(path.0.as_str())));
+    }
+}
+
+// replace rule and reset simulation when the rule file is loaded or saved ; invalid rules are ignored
+fn reload_rule(mut events: EventReader<AssetEvent<RuleAsset>>, assets: Res<Assets<RuleAsset>>, mut rule: ResMut<Rule>,
+               mut simulations: ResMut<Simulations>) {
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created {handle} | AssetEvent::Modified {handle} => {
+                if let Some(asset) = assets.get(handle) {
+                    if let Err(error) = asset.0.validate().and_then(|_| simulations.check_rule(&asset.0)) {
+                        error!("invalid rule '{}': {}", asset.0, error);
+                        continue;
+                    }
+
+                    *rule = asset.0.clone();
+                    simulations.reset(&rule);
+                }
+            },
+            AssetEvent::Removed {..} => {}
+        }
+    }
+}
+
+pub struct RuleFilePlugin;
+
+impl Plugin for RuleFilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<RuleAsset>()
+            .init_asset_loader::<RuleAssetLoader>()
+            .add_startup_system(load_rule_file)
+            .add_system(reload_rule);
+    }
+}
@@ -0,0 +1,193 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * March 16, 2023
+ */
+
+use rand::SeedableRng;
+
+use crate::{
+    cycles::{Cycle, CycleDetector},
+    engine::Simulation,
+    history::History,
+    recording::Recording,
+    rule::Rule,
+    seeding::SeedRng,
+    stats::Stats
+};
+
+// what is kept of a simulation from one reset to the next : the random number generator noise is drawn from and what
+// follows every generation stepped ; kept apart from the simulation so both can be borrowed at once
+pub struct Run {
+    pub seed: u64,
+    pub rng: SeedRng,
+    pub recording: Option<Recording>,
+    pub history: History,
+    pub stats: Stats,
+    pub cycles: CycleDetector
+}
+
+impl Run {
+    // create new Run starting from seed
+    pub fn new(seed: u64, history: History, stats: Stats, cycles: CycleDetector) -> Run {
+        Run {
+            seed,
+            rng: SeedRng::seed_from_u64(seed),
+            recording: None,
+            history,
+            stats,
+            cycles
+        }
+    }
+
+    // start over from seed with the random number generator at rng_position, forgetting every generation stepped ;
+    // a recording is left for the caller to save
+    pub fn restart(&mut self, seed: u64, rng_position: u128) {
+        self.seed = seed;
+        self.rng = SeedRng::seed_from_u64(seed);
+        self.rng.set_word_pos(rng_position);
+        self.history.clear();
+        self.stats.clear();
+        self.cycles.clear();
+    }
+
+    // seed noise when the rule's seeding policy asks for it, then step simulation ; returns the cycle it settled into
+    // when this step is the first repeat of an earlier state
+    pub fn step(&mut self, simulation: &mut dyn Simulation, rule: &Rule) -> Option<Cycle> {
+        let mut cycle = None;
+
+        // the generation history starts from the first generation stepped
+        if self.history.is_empty() {
+            self.history.push(simulation, None, self.rng.get_word_pos());
+        }
+
+        simulation.record_changes(true);
+
+        if rule.seeding.seeds_at(simulation.generation()) {
+            simulation.seed(rule, &rule.seeding.positions(simulation.center(), simulation.bounds(), &mut self.rng));
+        }
+
+        simulation.step(rule);
+
+        let changes = simulation.take_changes();
+
+        self.stats.record(simulation, rule, &changes);
+
+        // hashing starts after the first step, as changes made to the cells in between steps arrive with it ; noise
+        // seeded along the way breaks cycles, so detection stops and starts over once the rule stops seeding
+        if rule.seeding.seeds_after_start() {
+            self.cycles.clear();
+        } else if self.cycles.is_empty() {
+            self.cycles.start(simulation, rule);
+        } else {
+            cycle = self.cycles.push(simulation.generation(), &changes);
+        }
+
+        if let Some(recording) = &mut self.recording {
+            recording.push(changes.clone());
+        }
+
+        self.history.push(simulation, Some(changes), self.rng.get_word_pos());
+
+        cycle
+    }
+
+    // go back generations in the history of simulation ; stepping on resumes from there with the same noise, returns
+    // false when there was nothing to go back to
+    pub fn rewind(&mut self, generations: u64, simulation: &mut dyn Simulation, rule: &Rule) -> bool {
+        match self.history.rewind(generations, simulation, rule) {
+            Some(rng_position) => {
+                self.rng = SeedRng::seed_from_u64(self.seed);
+                self.rng.set_word_pos(rng_position);
+                self.stats.truncate(simulation.generation());
+                self.cycles.clear();
+
+                true
+            },
+            None => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_math::IVec3;
+
+    use super::*;
+    use crate::{
+        generators::Generator,
+        history::HistoryMode,
+        multi_threading::MultiThreaded,
+        seeding::SeedPolicy
+    };
+
+    fn run() -> Run {
+        Run::new(3, History::new(HistoryMode::Delta(8), usize::MAX), Stats::new(1000), CycleDetector::new(64))
+    }
+
+    fn simulation(rule: &Rule) -> MultiThreaded {
+        let mut simulation = MultiThreaded::new();
+
+        simulation.set_bounds(rule.bounding_size);
+        simulation
+    }
+
+    fn state(simulation: &dyn Simulation) -> Vec<(IVec3, u8)> {
+        let mut cells: Vec<(IVec3, u8)> = simulation.cells().map(|(position, cell)| (position, cell.value)).collect();
+
+        cells.sort_by_key(|(position, _)| position.to_array());
+        cells
+    }
+
+    #[test]
+    fn rewound_runs_step_on_with_the_same_noise() {
+        let mut rule = "9-26/5-7,12-13,15/5/M".parse::<Rule>().unwrap();
+
+        rule.bounding_size = IVec3::splat(32);
+        rule.seeding.policy = SeedPolicy::Every(4);
+
+        let mut run = run();
+        let mut simulation = simulation(&rule);
+        let mut states = vec![];
+
+        for _ in 0..24 {
+            run.step(&mut simulation, &rule);
+            states.push(state(&simulation));
+        }
+
+        assert!(run.rewind(10, &mut simulation, &rule));
+        assert_eq!(run.stats.series.len(), 14);
+
+        for expected in &states[14..] {
+            run.step(&mut simulation, &rule);
+
+            assert_eq!(&state(&simulation), expected);
+        }
+    }
+
+    #[test]
+    fn skips_cycle_detection_while_seeding() {
+        // the single seeded cell dies before the next one is seeded, which repeats the same state every generation
+        let mut rule = "/6/1/M".parse::<Rule>().unwrap();
+
+        rule.bounding_size = IVec3::splat(16);
+        rule.seeding.generator = Generator::SingleCell;
+        rule.seeding.policy = SeedPolicy::Continuous;
+
+        let mut run = run();
+        let mut simulation = simulation(&rule);
+
+        for _ in 0..8 {
+            assert_eq!(run.step(&mut simulation, &rule), None);
+        }
+
+        assert!(run.cycles.is_empty());
+
+        // once the rule stops seeding, detection starts over and finds the empty world
+        rule.seeding.policy = SeedPolicy::OnReset;
+
+        let cycles = (0..3).map(|_| run.step(&mut simulation, &rule)).collect::<Vec<_>>();
+
+        assert_eq!(cycles, [None, Some(Cycle {start: 9, period: 1}), None]);
+    }
+}
@@ -0,0 +1,86 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * March 2, 2023
+ */
+
+use std::fmt;
+
+use bevy_math::IVec3;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use serde::{Deserialize, Serialize};
+
+use crate::generators::Generator;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SeedPolicy {
+    OnReset,
+    Every(u32),
+    Continuous
+}
+
+impl fmt::Display for SeedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeedPolicy::OnReset => write!(f, "seeded on reset"),
+            SeedPolicy::Every(interval) => write!(f, "seeded every {} generations", interval),
+            SeedPolicy::Continuous => write!(f, "seeded every generation")
+        }
+    }
+}
+
+// random number generator behind every random feature ; the same seed always gives the same sequence
+pub type SeedRng = ChaCha8Rng;
+
+// when and what is injected into a simulation
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Seeding {
+    pub seed: Option<u64>,
+    pub policy: SeedPolicy,
+    pub offset: IVec3,
+    pub generator: Generator
+}
+
+// implement Default trait for Seeding, a 13 x 13 x 13 cube of noise at the center before every generation
+impl Default for Seeding {
+    fn default() -> Self {
+        Seeding {
+            seed: None,
+            policy: SeedPolicy::Continuous,
+            offset: IVec3::ZERO,
+            generator: Generator::default()
+        }
+    }
+}
+
+impl Seeding {
+    // create generator from the configured seed, or from a random seed if there is none
+    pub fn rng(&self) -> (u64, SeedRng) {
+        let seed = self.seed.unwrap_or_else(rand::random);
+
+        (seed, SeedRng::seed_from_u64(seed))
+    }
+
+    // returns true if noise is injected before stepping from generation ; every policy seeds generation 0
+    pub fn seeds_at(&self, generation: u64) -> bool {
+        match self.policy {
+            SeedPolicy::OnReset => generation == 0,
+            SeedPolicy::Every(interval) => generation.is_multiple_of(interval.max(1) as u64),
+            SeedPolicy::Continuous => true
+        }
+    }
+
+    // returns true if noise is injected after generation 0, so a repeated state no longer means the run cycles
+    pub fn seeds_after_start(&self) -> bool {
+        self.policy != SeedPolicy::OnReset
+    }
+
+    // get positions of the generator's live cells around center of the world
+    pub fn positions(&self, center: IVec3, bounds: IVec3, rng: &mut SeedRng) -> Vec<IVec3> {
+        self.generator.positions(center + self.offset, bounds, rng)
+    }
+}
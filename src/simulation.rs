@@ -5,78 +5,585 @@
  */
 
 use bevy::{
-    prelude::{App, Input, KeyCode, Plugin, Res, ResMut, Query},
-    tasks::{AsyncComputeTaskPool, TaskPool}
+    core::Time,
+    ecs::schedule::SystemLabel,
+    math::IVec3,
+    prelude::{error, info, App, Input, KeyCode, Local, ParallelSystemDescriptorCoercion, Plugin, Res, ResMut, Query},
+    window::Windows
+};
+
+use cellular_automata::{
+    cycles::{Cycle, CycleDetector},
+    engine::Simulation,
+    generators::Generator,
+    history::{History, HistoryMode},
+    mesh::Mesh,
+    pattern::Pattern,
+    recording::{Recording, Replay},
+    rule::{Rule, RuleError},
+    run::Run,
+    seeding::SeedPolicy,
+    snapshot::{Snapshot, SnapshotError},
+    stats::{Stats, StatsFormat},
+    volume::{VolumeFormat, VolumeSeries},
+    vox::Vox
 };
 
 use crate::{
     cell_renderer::{InstanceData, InstanceMaterialData},
-    rule::Rule
+    clock::SimulationClock
 };
 
-pub trait Simulation: Send + Sync {
-    fn update(&mut self, input: &Input<KeyCode>, rule: &Rule, task_pool: &TaskPool);
-    fn render(&self, rule: &Rule, data: &mut Vec<InstanceData>);
-    fn reset(&mut self);
-    fn cell_count(&self) -> usize;
-    fn set_bounds(&mut self, new_bounds: i32) -> i32;
-    fn bounds(&self) -> i32;
+// file the snapshot keys save to and load from, in the working directory
+const SNAPSHOT_PATH: &str = "snapshot.ca3d";
+// file the pattern keys export to and import from, in the working directory
+const PATTERN_PATH: &str = "pattern.rle3";
+// file the voxel keys export to and import from, in the working directory
+const VOX_PATH: &str = "export.vox";
+// files the mesh key exports to, in the working directory
+const MESH_PATHS: [&str; 3] = ["export.obj", "export.stl", "export.ply"];
+// directory the volume keys export to and number of generations in a series
+const VOLUME_DIRECTORY: &str = "volumes";
+const VOLUME_SERIES_LENGTH: u64 = 64;
+// file the recording keys save to and replay from, in the working directory
+const RECORDING_PATH: &str = "recording.ca3r";
+// generations kept between full states in delta history, and memory the history may use
+const HISTORY_KEYFRAME_INTERVAL: u32 = 32;
+const HISTORY_BUDGET: usize = 256 * 1024 * 1024;
+// generations the rewind key goes back
+const REWIND_GENERATIONS: u64 = 50;
+// file the stats key exports to, in the working directory, with the extension of every format
+const STATS_PATH: &str = "stats";
+// generations the stats keep, the oldest are dropped beyond it ; a few hundred bytes each
+const STATS_CAPACITY: usize = 100_000;
+// generations the cycle detector remembers, the longest period it can find
+const CYCLE_CAPACITY: usize = 4096;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+pub enum SimulationSystem {
+    Control,
+    Step,
+    Render
 }
 
 pub struct Simulations {
     simulations: Vec<(String, Box<dyn Simulation>)>,
     active_simulation: Option<usize>,
-    bounds: i32,
+    run: Run,
+    replay: Option<Replay>,
+    pause_on_cycle: bool
 }
 
 impl Simulations {
     // create new Simulations
     pub fn new() -> Simulations {
+        let history = History::new(HistoryMode::Delta(HISTORY_KEYFRAME_INTERVAL), HISTORY_BUDGET);
+
         Simulations {
             simulations: vec![],
             active_simulation: None,
-            bounds: 64,
+            run: Run::new(0, history, Stats::new(STATS_CAPACITY), CycleDetector::new(CYCLE_CAPACITY)),
+            replay: None,
+            pause_on_cycle: false
         }
     }
 
     pub fn add_simulation(&mut self, name: String, simulation: Box<dyn Simulation>) {
         self.simulations.push((name, simulation));
     }
+
+    // get the active simulation
+    pub fn active(&self) -> Option<&dyn Simulation> {
+        self.active_simulation.map(|active| self.simulations[active].1.as_ref())
+    }
+
+    // get the active simulation mutably
+    pub fn active_mut(&mut self) -> Option<&mut Box<dyn Simulation>> {
+        self.active_simulation.map(move |active| &mut self.simulations[active].1)
+    }
+
+    // get the active simulation together with the run following it, to step it from outside
+    pub fn active_and_run(&mut self) -> Option<(&mut dyn Simulation, &mut Run)> {
+        match self.active_simulation {
+            Some(active) => Some((self.simulations[active].1.as_mut(), &mut self.run)),
+            None => None
+        }
+    }
+
+    // check the active simulation can run rule
+    pub fn check_rule(&self, rule: &Rule) -> Result<(), RuleError> {
+        self.active().map_or(Ok(()), |simulation| simulation.check_rule(rule))
+    }
+
+    // switch to simulation at index and reset it, unless it cannot run rule
+    pub fn select(&mut self, index: usize, rule: &Rule) -> Result<(), RuleError> {
+        self.simulations[index].1.check_rule(rule)?;
+        self.active_simulation = Some(index);
+        self.reset(rule);
+
+        Ok(())
+    }
+
+    // clear the active simulation and restart the random number generator from the rule's seed ; ends recording + replay
+    pub fn reset(&mut self, rule: &Rule) {
+        self.stop_recording(RECORDING_PATH);
+        self.replay = None;
+
+        let (seed, _) = rule.seeding.rng();
+
+        self.run.restart(seed, 0);
+
+        info!("seed: {}", seed);
+
+        if let Some(simulation) = self.active_mut() {
+            simulation.clear();
+        }
+    }
+
+    // save the active simulation together with the rule and random number generator state
+    pub fn save_snapshot(&self, rule: &Rule, path: &str) -> Result<(), SnapshotError> {
+        if let Some(simulation) = self.active() {
+            simulation.snapshot(rule, self.run.seed, self.run.rng.get_word_pos()).save(path)?;
+        }
+
+        Ok(())
+    }
+
+    // load snapshot into the active simulation, returning the rule it was saved with
+    pub fn load_snapshot(&mut self, path: &str) -> Result<Rule, SnapshotError> {
+        let snapshot = Snapshot::load(path)?;
+
+        self.check_rule(&snapshot.rule).map_err(|error| SnapshotError::InvalidRule(error.to_string()))?;
+        self.stop_recording(RECORDING_PATH);
+        self.replay = None;
+        self.run.restart(snapshot.seed, snapshot.rng_position);
+
+        if let Some(simulation) = self.active_mut() {
+            simulation.load_snapshot(&snapshot);
+        }
+
+        Ok(snapshot.rule)
+    }
+
+    // go back generations in the history of the active simulation ; stepping on resumes from there with the same noise
+    pub fn rewind(&mut self, generations: u64, rule: &Rule) -> bool {
+        let rewound = match self.active_and_run() {
+            Some((simulation, run)) => run.rewind(generations, simulation, rule),
+            None => false
+        };
+
+        // a recording cannot follow a rewind
+        if rewound {
+            self.stop_recording(RECORDING_PATH);
+        }
+
+        rewound
+    }
+
+    // get cycle the active simulation settled into, if found since the last reset
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.run.cycles.cycle()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.run.recording.is_some()
+    }
+
+    // start recording the active simulation from its current generation
+    pub fn start_recording(&mut self, rule: &Rule) {
+        let (seed, rng_position) = (self.run.seed, self.run.rng.get_word_pos());
+
+        self.run.recording = self.active().map(|simulation| Recording::start(simulation, rule, seed, rng_position));
+    }
+
+    // stop recording and save the recording to path, if there is one
+    pub fn stop_recording(&mut self, path: &str) {
+        if let Some(recording) = self.run.recording.take() {
+            match recording.save(path) {
+                Ok(()) => info!("saved {} recorded generations to '{}'", recording.frames.len(), path),
+                Err(error) => error!("failed to save recording '{}': {}", path, error)
+            }
+        }
+    }
+
+    pub fn replay(&self) -> Option<&Replay> {
+        self.replay.as_ref()
+    }
+
+    // replay recording at path in the active simulation, returning the rule it was recorded with
+    pub fn start_replay(&mut self, path: &str) -> Result<Rule, SnapshotError> {
+        let recording = Recording::load(path)?;
+
+        self.stop_recording(RECORDING_PATH);
+        self.run.restart(recording.start.seed, recording.start.rng_position);
+        self.replay = Some(Replay::new(recording));
+        self.seek_replay(0);
+
+        Ok(self.replay.as_ref().unwrap().rule().clone())
+    }
+
+    // show generation of the replay in the active simulation
+    pub fn seek_replay(&mut self, generation: u64) {
+        if let (Some(replay), Some(active)) = (self.replay.as_mut(), self.active_simulation) {
+            replay.seek(generation);
+            replay.show(self.simulations[active].1.as_mut());
+        }
+    }
+
+    // seed noise when the rule's seeding policy asks for it, then step the active simulation ; returns the cycle it
+    // settled into when this step is the first repeat of an earlier state
+    pub fn step(&mut self, rule: &Rule) -> Option<Cycle> {
+        let cycle = self.active_and_run().and_then(|(simulation, run)| run.step(simulation, rule));
+
+        if let Some(cycle) = cycle {
+            info!("{}", cycle);
+        }
+
+        cycle
+    }
 }
 
-pub fn update(mut this: ResMut<Simulations>, rule: Res<Rule>, input: Res<Input<KeyCode>>, mut query: Query<&mut InstanceMaterialData>,
-              task_pool: Res<AsyncComputeTaskPool>) {
-    let mut new_active = None;
+// returns true if either shift key is held
+fn shift_pressed(input: &Input<KeyCode>) -> bool {
+    input.pressed(KeyCode::LShift) || input.pressed(KeyCode::RShift)
+}
 
+// reset, switch initial condition generator and switch simulation
+pub fn control(mut this: ResMut<Simulations>, mut rule: ResMut<Rule>, input: Res<Input<KeyCode>>) {
     // default to simulation 1 on launch
-    if this.active_simulation == None {
-        new_active = Some(0);
+    if this.active_simulation.is_none() {
+        if let Err(error) = this.select(0, &rule) {
+            error!("cannot run '{}' in {}: {}", *rule, this.simulations[0].0, error);
+        }
     }
 
     // reset simulation when user presses 'R'
     if input.just_pressed(KeyCode::R) {
-        new_active = Some(0);
+        this.reset(&rule);
+    }
+
+    // switch to next initial condition generator and reset when user presses 'G' ; initial conditions only seed the
+    // first generation, they would otherwise be stamped again before every step, so the policy shown in the title
+    // switches to seeding on reset
+    if input.just_pressed(KeyCode::G) {
+        let generators = Generator::all();
+        let current = generators.iter().position(|generator| *generator == rule.seeding.generator);
+        let next = generators[current.map_or(0, |current| (current + 1) % generators.len())];
+
+        rule.seeding.generator = next;
+        rule.seeding.policy = SeedPolicy::OnReset;
+
+        info!("generator: {}, {}", next.name(), rule.seeding.policy);
+
+        this.reset(&rule);
+    }
+
+    // switch to next simulation when user presses 'Tab'
+    if input.just_pressed(KeyCode::Tab) {
+        let next = this.active_simulation.map_or(0, |active| (active + 1) % this.simulations.len());
+
+        match this.select(next, &rule) {
+            Ok(()) => info!("simulation: {}", this.simulations[next].0),
+            Err(error) => error!("cannot run '{}' in {}: {}", *rule, this.simulations[next].0, error)
+        }
+    }
+}
+
+// save snapshot when user presses 'F5', load it when user presses 'F9'
+pub fn snapshot_control(mut this: ResMut<Simulations>, mut rule: ResMut<Rule>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::F5) {
+        match this.save_snapshot(&rule, SNAPSHOT_PATH) {
+            Ok(()) => info!("saved snapshot to '{}'", SNAPSHOT_PATH),
+            Err(error) => error!("failed to save snapshot '{}': {}", SNAPSHOT_PATH, error)
+        }
+    }
+
+    if input.just_pressed(KeyCode::F9) {
+        match this.load_snapshot(SNAPSHOT_PATH) {
+            Ok(loaded_rule) => {
+                info!("loaded snapshot from '{}'", SNAPSHOT_PATH);
+                *rule = loaded_rule;
+            },
+            Err(error) => error!("failed to load snapshot '{}': {}", SNAPSHOT_PATH, error)
+        }
+    }
+}
+
+// export to and import from patterns, voxels, meshes, stats and volumes
+pub fn export_control(mut this: ResMut<Simulations>, rule: Res<Rule>, input: Res<Input<KeyCode>>) {
+    // export live cells as a pattern when user presses 'P', stamp it at the center when user presses 'I'
+    if input.just_pressed(KeyCode::P) {
+        if let Some(simulation) = this.active() {
+            match Pattern::from_simulation(simulation, &rule, None).save(PATTERN_PATH) {
+                Ok(()) => info!("exported pattern to '{}'", PATTERN_PATH),
+                Err(error) => error!("failed to export pattern '{}': {}", PATTERN_PATH, error)
+            }
+        }
+    }
+
+    // importing changes the cells under the replay, which only sets the cells it changes itself
+    let importing = input.just_pressed(KeyCode::I) || (input.just_pressed(KeyCode::V) && shift_pressed(&input));
+
+    if importing && this.replay().is_some() {
+        info!("stop the replay to import into the simulation");
+        return;
+    }
+
+    if input.just_pressed(KeyCode::I) {
+        match Pattern::load(PATTERN_PATH) {
+            Ok(pattern) => {
+                let seeded = this.active_mut().map(|simulation| {
+                    let position = simulation.center() - pattern.size / 2;
+
+                    pattern.stamp(simulation.as_mut(), &rule, position)
+                });
+
+                // a pattern with decaying cells replaces the cells wholesale, which a recording cannot follow
+                if seeded == Some(false) {
+                    this.stop_recording(RECORDING_PATH);
+                    this.run.history.clear();
+                    this.run.cycles.clear();
+                }
+
+                info!("imported pattern from '{}'", PATTERN_PATH);
+            },
+            Err(error) => error!("failed to import pattern '{}': {}", PATTERN_PATH, error)
+        }
+    }
+
+    // export live cells as a .vox file when user presses 'V', seed its first model at the center when user presses 'Shift + V'
+    if input.just_pressed(KeyCode::V) && !shift_pressed(&input) {
+        if let Some(simulation) = this.active() {
+            match Vox::from_simulation(simulation, &rule).save(VOX_PATH) {
+                Ok(()) => info!("exported voxels to '{}'", VOX_PATH),
+                Err(error) => error!("failed to export voxels '{}': {}", VOX_PATH, error)
+            }
+        }
+    }
+
+    if input.just_pressed(KeyCode::V) && shift_pressed(&input) {
+        let imported = Vox::load(VOX_PATH).and_then(|vox| match this.active_mut() {
+            Some(simulation) => {
+                let position = simulation.center() - vox.models.first().map_or(IVec3::ZERO, |model| model.size / 2);
+
+                vox.seed(simulation.as_mut(), &rule, 0, position)
+            },
+            None => Ok(())
+        });
+
+        match imported {
+            Ok(()) => info!("imported voxels from '{}'", VOX_PATH),
+            Err(error) => error!("failed to import voxels '{}': {}", VOX_PATH, error)
+        }
+    }
+
+    // export surface mesh of the live cells as OBJ, STL and PLY when user presses 'M'
+    if input.just_pressed(KeyCode::M) {
+        if let Some(simulation) = this.active() {
+            let mesh = Mesh::from_simulation(simulation, &rule);
+            let results = [mesh.save_obj(MESH_PATHS[0]), mesh.save_stl(MESH_PATHS[1]), mesh.save_ply(MESH_PATHS[2])];
+
+            for (path, result) in MESH_PATHS.iter().zip(results) {
+                match result {
+                    Ok(()) => info!("exported mesh to '{}'", path),
+                    Err(error) => error!("failed to export mesh '{}': {}", path, error)
+                }
+            }
+        }
+    }
+
+    // export population numbers of every generation as CSV and JSON when user presses 'T'
+    if input.just_pressed(KeyCode::T) {
+        for format in [StatsFormat::Csv, StatsFormat::Json] {
+            let path = format!("{}.{}", STATS_PATH, format.extension());
+
+            match this.run.stats.save(&path, format) {
+                Ok(()) => info!("exported stats of {} generations to '{}'", this.run.stats.series.len(), path),
+                Err(error) => error!("failed to export stats '{}': {}", path, error)
+            }
+        }
+    }
+
+    // export the state grid as VTK and NumPy volumes when user presses 'X', the next 64 generations when user presses 'Shift + X'
+    if input.just_pressed(KeyCode::X) {
+        let series = VolumeSeries {
+            directory: VOLUME_DIRECTORY.into(),
+            prefix: "generation".into(),
+            formats: vec![VolumeFormat::Vti, VolumeFormat::Npy],
+            neighbours: true
+        };
+        let count = if shift_pressed(&input) { VOLUME_SERIES_LENGTH } else { 1 };
+
+        // a series steps the simulation, which would leave the replay showing generations it did not record
+        if count > 1 && this.replay().is_some() {
+            info!("stop the replay to export a series of volumes");
+        } else if let Some((simulation, run)) = this.active_and_run() {
+            let generation = simulation.generation();
+            let result = series.write_range(simulation, generation..=generation + count - 1, |simulation| {
+                if let Some(cycle) = run.step(simulation, &rule) {
+                    info!("{}", cycle);
+                }
+            });
+
+            match result {
+                Ok(()) => info!("exported {} volumes to '{}'", count, VOLUME_DIRECTORY),
+                Err(error) => error!("failed to export volumes to '{}': {}", VOLUME_DIRECTORY, error)
+            }
+        }
+    }
+}
+
+// start / stop recording when user presses 'C', replay the recording when user presses 'L', scrub through the replay
+// with 'Left' / 'Right' and jump to its start / end with 'Home' / 'End'
+pub fn replay_control(mut this: ResMut<Simulations>, mut rule: ResMut<Rule>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::C) {
+        if this.is_recording() {
+            this.stop_recording(RECORDING_PATH);
+        } else {
+            this.start_recording(&rule);
+            info!("recording");
+        }
+    }
+
+    if input.just_pressed(KeyCode::L) {
+        match this.start_replay(RECORDING_PATH) {
+            Ok(recorded_rule) => {
+                info!("replaying '{}'", RECORDING_PATH);
+                *rule = recorded_rule;
+            },
+            Err(error) => error!("failed to replay '{}': {}", RECORDING_PATH, error)
+        }
+    }
+
+    if let Some(generation) = this.replay().map(|replay| replay.generation()) {
+        let target = if input.just_pressed(KeyCode::Left) {
+            Some(generation.saturating_sub(1))
+        } else if input.just_pressed(KeyCode::Right) {
+            Some(generation + 1)
+        } else if input.just_pressed(KeyCode::Home) {
+            Some(0)
+        } else if input.just_pressed(KeyCode::End) {
+            Some(u64::MAX)
+        } else {
+            None
+        };
+
+        if let Some(target) = target {
+            this.seek_replay(target);
+        }
     }
+}
 
-    if let Some(new_active) = new_active {
-        this.active_simulation = Some(new_active);
-        this.simulations[new_active].1.reset();
+// step back one generation when user presses 'Backspace', rewind 50 when user presses 'Shift + Backspace', switch the
+// history between full states and deltas when user presses 'H'
+pub fn history_control(mut this: ResMut<Simulations>, mut clock: ResMut<SimulationClock>, rule: Res<Rule>,
+                       input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::Back) && this.replay().is_none() {
+        let generations = if shift_pressed(&input) { REWIND_GENERATIONS } else { 1 };
+
+        if this.rewind(generations, &rule) {
+            clock.playing = false;
+        }
     }
 
-    if let Some(active) = this.active_simulation {
-        let bounds = this.bounds;
-        let simulation = &mut this.simulations[active].1;
-        let new_bounds = simulation.set_bounds(bounds);
+    if input.just_pressed(KeyCode::H) {
+        let mode = match this.run.history.mode() {
+            HistoryMode::Full => HistoryMode::Delta(HISTORY_KEYFRAME_INTERVAL),
+            HistoryMode::Delta(_) => HistoryMode::Full
+        };
 
-        simulation.update(&input, &rule, &task_pool.0);
+        info!("history: {}", if mode == HistoryMode::Full { "full states" } else { "deltas" });
 
+        this.run.history.set_mode(mode);
+    }
+}
+
+// switch pausing once the run settles into a still life or cycle when user presses 'O'
+pub fn cycle_control(mut this: ResMut<Simulations>, mut clock: ResMut<SimulationClock>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::O) {
+        this.pause_on_cycle = !this.pause_on_cycle;
+
+        info!("pause on cycle: {}", if this.pause_on_cycle { "on" } else { "off" });
+
+        // the run may have settled before pausing was switched on
+        if this.pause_on_cycle && this.cycle().is_some() {
+            clock.playing = false;
+        }
+    }
+}
+
+// play / pause when user presses 'Space', step once when user presses 'S' and change speed when user presses 'Up' or
+// 'Down', then advance the active simulation by the generations due this frame
+pub fn clock_control(mut this: ResMut<Simulations>, mut clock: ResMut<SimulationClock>, rule: Res<Rule>, input: Res<Input<KeyCode>>,
+                     time: Res<Time>) {
+    if input.just_pressed(KeyCode::Space) {
+        clock.toggle();
+    }
+
+    if input.just_pressed(KeyCode::S) {
+        clock.step_once();
+    }
+
+    if input.just_pressed(KeyCode::Up) {
+        clock.faster();
+    }
+
+    if input.just_pressed(KeyCode::Down) {
+        clock.slower();
+    }
+
+    let steps = clock.tick(time.delta_seconds());
+
+    if let Some(simulation) = this.active_mut() {
+        simulation.set_bounds(rule.bounding_size);
+    }
+
+    // while replaying, the clock moves through the recording instead of simulating
+    if let Some(generation) = this.replay().map(|replay| replay.generation()) {
+        if steps > 0 {
+            this.seek_replay(generation + steps as u64);
+        }
+
+        return;
+    }
+
+    for _ in 0..steps {
+        if this.step(&rule).is_some() && this.pause_on_cycle {
+            clock.playing = false;
+            break;
+        }
+    }
+}
+
+// show seed, generator and seeding policy of the current run in the window title
+pub fn show_seed(this: Res<Simulations>, rule: Res<Rule>, mut windows: ResMut<Windows>, mut shown_title: Local<String>) {
+    let title = format!("3D Cellular Automata - seed {} - {}, {}", this.run.seed, rule.seeding.generator.name(), rule.seeding.policy);
+
+    if *shown_title == title {
+        return;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_title(title.clone());
+        *shown_title = title;
+    }
+}
+
+// copy live cells of the active simulation into the instance data
+pub fn render(this: Res<Simulations>, rule: Res<Rule>, mut query: Query<&mut InstanceMaterialData>) {
+    if let Some(simulation) = this.active() {
         let mut instance_data = query.iter_mut().next().unwrap();
+        let center = simulation.center();
 
         instance_data.0.clear();
 
-        simulation.render(&rule, &mut instance_data.0);
-
-        this.bounds = new_bounds;
+        for (position, cell) in simulation.cells() {
+            instance_data.0.push(InstanceData {
+                position: (position - center).as_vec3(),
+                scale: 1.0,
+                colour: simulation.colour(&rule, position, cell).as_rgba_f32()
+            });
+        }
     }
 }
 
@@ -84,6 +591,16 @@ pub struct SimulationsPlugin;
 
 impl Plugin for SimulationsPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Simulations::new()).add_system(update);
+        app.insert_resource(Simulations::new())
+            .init_resource::<SimulationClock>()
+            .add_system(control.label(SimulationSystem::Control))
+            .add_system(snapshot_control.label(SimulationSystem::Control))
+            .add_system(export_control.label(SimulationSystem::Control))
+            .add_system(replay_control.label(SimulationSystem::Control))
+            .add_system(history_control.label(SimulationSystem::Control))
+            .add_system(cycle_control.label(SimulationSystem::Control))
+            .add_system(clock_control.label(SimulationSystem::Step).after(SimulationSystem::Control))
+            .add_system(render.label(SimulationSystem::Render).after(SimulationSystem::Step))
+            .add_system(show_seed.after(SimulationSystem::Step));
     }
 }
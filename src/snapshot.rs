@@ -0,0 +1,389 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * March 8, 2023
+ */
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path
+};
+
+use bevy_math::{ivec3, IVec3};
+
+use crate::rule::Rule;
+
+const MAGIC: &[u8; 8] = b"CA3DSNAP";
+pub const VERSION: u32 = 1;
+
+// full state of a run ; neighbour counts are left out as they are recomputed from the values on load
+#[derive(Clone)]
+pub struct Snapshot {
+    pub rule: Rule,
+    pub bounds: IVec3,
+    pub generation: u64,
+    pub seed: u64,
+    // position of the random number generator within the seed's stream, so noise continues where it left off
+    pub rng_position: u128,
+    // xyz position relative to the center of the world and value of every live cell, so any engine can load it
+    pub cells: Vec<(IVec3, u8)>
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    UnknownFormat,
+    UnsupportedVersion(u32),
+    Truncated,
+    ChecksumMismatch,
+    InvalidRule(String),
+    InvalidCell(IVec3, u8),
+    InvalidVarint
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "{}", error),
+            SnapshotError::UnknownFormat => write!(f, "file is not of the expected format, its header does not match"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "snapshot version {} is not supported, expected version {}", version, VERSION)
+            },
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::ChecksumMismatch => write!(f, "snapshot is corrupt, checksum does not match"),
+            SnapshotError::InvalidRule(error) => write!(f, "snapshot rule is invalid: {}", error),
+            SnapshotError::InvalidCell(position, value) => {
+                write!(f, "snapshot cell at {} has value {} which the rule's states do not allow", position, value)
+            },
+            SnapshotError::InvalidVarint => write!(f, "variable length integer is longer than 32 bits")
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> Self {
+        SnapshotError::Io(error)
+    }
+}
+
+// 64 bit FNV-1a hash, enough to tell a damaged file from an intact one
+pub fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+// reads little endian values from a byte slice, failing on truncation
+pub struct ByteReader<'a> {
+    bytes: &'a [u8]
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        ByteReader {
+            bytes
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn take(&mut self, count: usize) -> Result<&'a [u8], SnapshotError> {
+        if count > self.bytes.len() {
+            return Err(SnapshotError::Truncated);
+        }
+
+        let (taken, rest) = self.bytes.split_at(count);
+
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, SnapshotError> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn u128(&mut self) -> Result<u128, SnapshotError> {
+        Ok(u128::from_le_bytes(self.take(16)?.try_into().unwrap()))
+    }
+
+    pub fn ivec3(&mut self) -> Result<IVec3, SnapshotError> {
+        Ok(ivec3(self.i32()?, self.i32()?, self.i32()?))
+    }
+
+    // read length prefixed RON rule
+    pub fn rule(&mut self) -> Result<Rule, SnapshotError> {
+        let length = self.u32()? as usize;
+        let rule = ron::de::from_bytes::<Rule>(self.take(length)?).map_err(|error| SnapshotError::InvalidRule(error.to_string()))?;
+
+        rule.validate().map_err(|error| SnapshotError::InvalidRule(error.to_string()))?;
+
+        Ok(rule)
+    }
+}
+
+// append xyz position as little endian values
+pub fn write_ivec3(bytes: &mut Vec<u8>, position: IVec3) {
+    for axis in position.to_array() {
+        bytes.extend(axis.to_le_bytes());
+    }
+}
+
+// append rule as length prefixed RON
+pub fn write_rule(bytes: &mut Vec<u8>, rule: &Rule) {
+    let rule = ron::to_string(rule).expect("rule can always be serialized");
+
+    bytes.extend((rule.len() as u32).to_le_bytes());
+    bytes.extend(rule.as_bytes());
+}
+
+// wrap payload in magic, version, length and checksum
+pub fn write_framed<W: Write>(writer: &mut W, magic: &[u8; 8], version: u32, payload: &[u8]) -> io::Result<()> {
+    writer.write_all(magic)?;
+    writer.write_all(&version.to_le_bytes())?;
+    writer.write_all(&(payload.len() as u64).to_le_bytes())?;
+    writer.write_all(payload)?;
+    writer.write_all(&checksum(payload).to_le_bytes())?;
+
+    writer.flush()
+}
+
+// unwrap payload written by write_framed, rejecting other files, other versions and damaged payloads
+pub fn read_framed<R: Read>(reader: &mut R, magic: &[u8; 8], version: u32) -> Result<Vec<u8>, SnapshotError> {
+    let mut bytes = vec![];
+
+    reader.read_to_end(&mut bytes)?;
+
+    let mut reader = ByteReader::new(&bytes);
+
+    if reader.take(magic.len()).ok() != Some(&magic[..]) {
+        return Err(SnapshotError::UnknownFormat);
+    }
+
+    let found_version = reader.u32()?;
+
+    if found_version != version {
+        return Err(SnapshotError::UnsupportedVersion(found_version));
+    }
+
+    let length = reader.u64()? as usize;
+    let payload = reader.take(length)?;
+
+    if reader.u64()? != checksum(payload) || !reader.is_empty() {
+        return Err(SnapshotError::ChecksumMismatch);
+    }
+
+    Ok(payload.to_vec())
+}
+
+impl Snapshot {
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), SnapshotError> {
+        let mut payload = vec![];
+
+        self.write_payload(&mut payload);
+
+        Ok(write_framed(writer, MAGIC, VERSION, &payload)?)
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Snapshot, SnapshotError> {
+        let payload = read_framed(reader, MAGIC, VERSION)?;
+
+        Snapshot::read_payload(&mut ByteReader::new(&payload))
+    }
+
+    // append snapshot without framing, so other formats can embed it
+    pub fn write_payload(&self, payload: &mut Vec<u8>) {
+        write_rule(payload, &self.rule);
+        write_ivec3(payload, self.bounds);
+        payload.extend(self.generation.to_le_bytes());
+        payload.extend(self.seed.to_le_bytes());
+        payload.extend(self.rng_position.to_le_bytes());
+        payload.extend((self.cells.len() as u64).to_le_bytes());
+
+        for (position, value) in &self.cells {
+            write_ivec3(payload, *position);
+            payload.push(*value);
+        }
+    }
+
+    // read snapshot written by write_payload
+    pub fn read_payload(reader: &mut ByteReader) -> Result<Snapshot, SnapshotError> {
+        let rule = reader.rule()?;
+        let bounds = reader.ivec3()?;
+        let generation = reader.u64()?;
+        let seed = reader.u64()?;
+        let rng_position = reader.u128()?;
+        let count = reader.u64()? as usize;
+
+        // every cell takes 13 bytes, check before allocating so a bad count cannot exhaust memory
+        if count > reader.remaining() / 13 {
+            return Err(SnapshotError::Truncated);
+        }
+
+        let mut cells = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let position = reader.ivec3()?;
+            let value = reader.u8()?;
+
+            if value == 0 || value > rule.states {
+                return Err(SnapshotError::InvalidCell(position, value));
+            }
+
+            cells.push((position, value));
+        }
+
+        Ok(Snapshot {
+            rule,
+            bounds,
+            generation,
+            seed,
+            rng_position,
+            cells
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        self.write(&mut BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Snapshot, SnapshotError> {
+        Snapshot::read(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::Simulation,
+        multi_threading::MultiThreaded,
+        sparse::Sparse
+    };
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            rule: "9-26/5-7,12-13,15/20/M".parse().unwrap(),
+            bounds: ivec3(40, 30, 20),
+            generation: 17,
+            seed: 1234,
+            rng_position: 5678,
+            cells: vec![(ivec3(0, 0, 0), 20), (ivec3(-3, 4, 9), 7), (ivec3(12, -14, 0), 1)]
+        }
+    }
+
+    fn bytes() -> Vec<u8> {
+        let mut bytes = vec![];
+
+        snapshot().write(&mut bytes).unwrap();
+
+        bytes
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let expected = snapshot();
+        let read = Snapshot::read(&mut &bytes()[..]).unwrap();
+
+        assert_eq!(ron::to_string(&read.rule).unwrap(), ron::to_string(&expected.rule).unwrap());
+        assert_eq!(read.bounds, expected.bounds);
+        assert_eq!(read.generation, expected.generation);
+        assert_eq!(read.seed, expected.seed);
+        assert_eq!(read.rng_position, expected.rng_position);
+        assert_eq!(read.cells, expected.cells);
+    }
+
+    #[test]
+    fn loads_across_engines() {
+        let snapshot = snapshot();
+        let mut bounded = MultiThreaded::new();
+        let mut sparse = Sparse::new();
+
+        bounded.load_snapshot(&snapshot);
+        sparse.load_snapshot(&snapshot);
+
+        for simulation in [&bounded as &dyn Simulation, &sparse] {
+            let mut cells = simulation.snapshot(&snapshot.rule, snapshot.seed, snapshot.rng_position).cells;
+
+            cells.sort_by_key(|(position, _)| position.to_array());
+
+            let mut expected = snapshot.cells.clone();
+
+            expected.sort_by_key(|(position, _)| position.to_array());
+
+            assert_eq!(cells, expected);
+            assert_eq!(simulation.generation(), snapshot.generation);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_file() {
+        let bytes = bytes();
+
+        for length in [0, 4, 10, 20, bytes.len() / 2, bytes.len() - 1] {
+            assert!(matches!(Snapshot::read(&mut &bytes[..length]), Err(SnapshotError::Truncated | SnapshotError::UnknownFormat)),
+                    "length {}", length);
+        }
+
+        assert!(matches!(Snapshot::read(&mut &bytes[..bytes.len() - 1]), Err(SnapshotError::Truncated)));
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        let mut bytes = bytes();
+        let payload = bytes.len() - 20;
+
+        bytes[payload] ^= 0xff;
+
+        assert!(matches!(Snapshot::read(&mut &bytes[..]), Err(SnapshotError::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = bytes();
+
+        bytes[0] = b'X';
+
+        assert!(matches!(Snapshot::read(&mut &bytes[..]), Err(SnapshotError::UnknownFormat)));
+    }
+
+    #[test]
+    fn rejects_other_version() {
+        let mut bytes = bytes();
+
+        bytes[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+        assert!(matches!(Snapshot::read(&mut &bytes[..]), Err(SnapshotError::UnsupportedVersion(version)) if version == VERSION + 1));
+    }
+
+    #[test]
+    fn rejects_cells_beyond_the_rule_states() {
+        let mut snapshot = snapshot();
+        let mut bytes = vec![];
+
+        snapshot.cells.push((ivec3(1, 1, 1), 21));
+        snapshot.write(&mut bytes).unwrap();
+
+        assert!(matches!(Snapshot::read(&mut &bytes[..]), Err(SnapshotError::InvalidCell(_, 21))));
+    }
+}
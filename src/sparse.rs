@@ -0,0 +1,548 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * February 15, 2023
+ */
+
+use std::collections::HashMap;
+
+use bevy_math::{ivec3, IVec3};
+use bevy_tasks::TaskPool;
+
+use futures_lite::future;
+
+use crate::{
+    chunks::CHUNK_SIZE,
+    engine::{Changes, Simulation},
+    multi_threading::{Cell, MultiThreaded},
+    rule::{Rule, RuleError}
+};
+
+type Chunk = crate::chunks::Chunk<Cell>;
+
+// offset of a cell within its chunk and whether its neighbour count goes up or down
+type HaloUpdate = (usize, bool);
+
+// neighbour updates for a chunk from its own cells and from cells across its border
+#[derive(Default)]
+struct NeighbourUpdates {
+    spawns: Vec<usize>,
+    deaths: Vec<usize>,
+    halo: Vec<Vec<HaloUpdate>>
+}
+
+// number of steps a chunk must stay empty before it is freed
+const EMPTY_STEPS_BEFORE_FREE: u32 = 8;
+
+struct SparseChunk {
+    cells: Chunk,
+    empty_steps: u32
+}
+
+impl SparseChunk {
+    // create new SparseChunk
+    fn new() -> Self {
+        SparseChunk {
+            cells: Chunk::default(),
+            empty_steps: 0
+        }
+    }
+
+    // returns true if no cell is alive or has a live neighbour ; false otherwise
+    fn is_empty(&self) -> bool {
+        self.cells.0.iter().all(|cell| cell.is_dead() && cell.neighbours == 0)
+    }
+}
+
+// split xyz position into chunk position and offset within that chunk
+fn split_position(position: IVec3) -> (IVec3, usize) {
+    let size = CHUNK_SIZE as i32;
+    let chunk_position = ivec3(position.x.div_euclid(size), position.y.div_euclid(size), position.z.div_euclid(size));
+    let offset = Chunk::position_to_index(position - chunk_position * size);
+
+    (chunk_position, offset)
+}
+
+// unbounded world which only holds the chunks live cells have reached
+pub struct Sparse {
+    tasks: TaskPool,
+    chunks: HashMap<IVec3, SparseChunk>,
+    generation: u64,
+    bounds: IVec3,
+    changes: Option<Changes>
+}
+
+// implement Default trait for Sparse
+impl Default for Sparse {
+    fn default() -> Self {
+        Sparse::new()
+    }
+}
+
+impl Sparse {
+    // create new Sparse with its own task pool
+    pub fn new() -> Self {
+        Sparse::with_task_pool(TaskPool::new())
+    }
+
+    // create new Sparse that runs its chunk tasks on the given task pool
+    pub fn with_task_pool(tasks: TaskPool) -> Self {
+        Sparse {
+            tasks,
+            chunks: HashMap::new(),
+            generation: 0,
+            bounds: IVec3::ZERO,
+            changes: None
+        }
+    }
+
+    // bounds are only used to scale distance based colours, the world itself is unbounded
+    pub fn set_bounds(&mut self, new_bounds: IVec3) -> IVec3 {
+        self.bounds = new_bounds;
+
+        self.bounds
+    }
+
+    pub fn bounds(&self) -> IVec3 {
+        self.bounds
+    }
+
+    // the world is centered on the origin
+    pub fn center(&self) -> IVec3 {
+        IVec3::ZERO
+    }
+
+    // get cell at xyz position ; cells outside the allocated chunks are dead
+    pub fn cell(&self, position: IVec3) -> Cell {
+        let (chunk_position, offset) = split_position(position);
+
+        self.chunks.get(&chunk_position).map(|chunk| chunk.cells.0[offset]).unwrap_or_default()
+    }
+
+    // iterate over xyz position and state of every live cell
+    pub fn cells(&self) -> impl Iterator<Item = (IVec3, Cell)> + '_ {
+        self.chunks.iter().flat_map(|(chunk_position, chunk)| {
+            let origin = *chunk_position * CHUNK_SIZE as i32;
+
+            chunk.cells.0.iter().enumerate()
+                .filter(|(_, cell)| !cell.is_dead())
+                .map(move |(offset, cell)| (origin + Chunk::index_to_position(offset), *cell))
+        })
+    }
+
+    // get number of generations stepped since the simulation was cleared
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.chunks.values()
+            .map(|chunk| chunk.cells.0.iter().filter(|cell| !cell.is_dead()).count())
+            .sum()
+    }
+
+    // update neighbours of the cell at xyz position, creating chunks as they are reached
+    fn update_neighbours(chunks: &mut HashMap<IVec3, SparseChunk>, rule: &Rule, position: IVec3, increment: bool) {
+        for dir in rule.neighbour_method.get_neighbour_iter() {
+            let (chunk_position, offset) = split_position(position + *dir);
+            let cell = &mut chunks.entry(chunk_position).or_insert_with(SparseChunk::new).cells.0[offset];
+
+            if increment {
+                cell.neighbours += 1;
+            } else {
+                cell.neighbours -= 1;
+            }
+        }
+    }
+
+    fn update_values_chunk(chunk: &mut Chunk, chunk_position: IVec3, rule: &Rule, chunk_spawns: &mut Vec<usize>,
+                           spawns: &mut Vec<IVec3>, chunk_deaths: &mut Vec<usize>, deaths: &mut Vec<IVec3>) {
+        let limit = IVec3::splat(CHUNK_SIZE as i32);
+        let origin = chunk_position * CHUNK_SIZE as i32;
+
+        for (offset, cell) in chunk.0.iter_mut().enumerate() {
+            let position = Chunk::index_to_position(offset);
+
+            if cell.is_dead() {
+                if rule.birth_rule.in_range(cell.neighbours) {
+                    cell.value = rule.states;
+
+                    if Chunk::is_border_position(position, limit) {
+                        spawns.push(origin + position);
+                    } else {
+                        chunk_spawns.push(offset);
+                    }
+                }
+            } else {
+                if cell.value < rule.states || !rule.survival_rule.in_range(cell.neighbours) {
+                    if cell.value == rule.states {
+                        if Chunk::is_border_position(position, limit) {
+                            deaths.push(origin + position);
+                        } else {
+                            chunk_deaths.push(offset);
+                        }
+                    }
+
+                    cell.value -= 1;
+                }
+            }
+        }
+    }
+
+    // bucket the neighbour updates of border spawns + deaths by the chunk they reach
+    fn bucket_halo(rule: &Rule, spawns: &[IVec3], deaths: &[IVec3]) -> Vec<(IVec3, Vec<HaloUpdate>)> {
+        let mut buckets: HashMap<IVec3, Vec<HaloUpdate>> = HashMap::new();
+
+        for (positions, increment) in [(spawns, true), (deaths, false)] {
+            for position in positions {
+                for dir in rule.neighbour_method.get_neighbour_iter() {
+                    let (chunk_position, offset) = split_position(*position + *dir);
+
+                    buckets.entry(chunk_position).or_default().push((offset, increment));
+                }
+            }
+        }
+
+        buckets.into_iter().collect()
+    }
+
+    // advance simulation by one generation
+    pub fn step(&mut self, rule: &Rule) {
+        let tasks = &self.tasks;
+        let chunks = std::mem::take(&mut self.chunks);
+
+        // update values
+        let mut value_tasks = vec![];
+
+        for (chunk_position, mut chunk) in chunks.into_iter() {
+            let rule = rule.clone();
+            let mut chunk_spawns = vec![];
+            let mut chunk_deaths = vec![];
+            let mut spawns = vec![];
+            let mut deaths = vec![];
+
+            value_tasks.push(tasks.spawn(async move {
+                Self::update_values_chunk(&mut chunk.cells, chunk_position, &rule, &mut chunk_spawns, &mut spawns, &mut chunk_deaths, &mut deaths);
+
+                let halo = Self::bucket_halo(&rule, &spawns, &deaths);
+
+                (chunk_position, chunk, chunk_spawns, spawns, chunk_deaths, deaths, halo)
+            }));
+        }
+
+        // collect spawns + deaths, with the neighbour updates reaching across chunk borders
+        let mut chunks = HashMap::with_capacity(value_tasks.len());
+        let mut updates: HashMap<IVec3, NeighbourUpdates> = HashMap::new();
+
+        for task in value_tasks {
+            let (chunk_position, chunk, in_spawns, out_spawns, in_deaths, out_deaths, halo) = future::block_on(task);
+
+            if let Some(changes) = &mut self.changes {
+                let origin = chunk_position * CHUNK_SIZE as i32;
+
+                changes.births.extend(in_spawns.iter().map(|offset| origin + Chunk::index_to_position(*offset)));
+                changes.births.extend(&out_spawns);
+                changes.deaths.extend(in_deaths.iter().map(|offset| origin + Chunk::index_to_position(*offset)));
+                changes.deaths.extend(&out_deaths);
+            }
+
+            chunks.insert(chunk_position, chunk);
+
+            if !in_spawns.is_empty() || !in_deaths.is_empty() {
+                let updates = updates.entry(chunk_position).or_default();
+
+                updates.spawns = in_spawns;
+                updates.deaths = in_deaths;
+            }
+
+            for (target_position, halo_updates) in halo {
+                updates.entry(target_position).or_default().halo.push(halo_updates);
+            }
+        }
+
+        // update neighbours in parallel, each chunk applying its own changes and those reaching it across its border ;
+        // chunks live cells reach for the first time are allocated here
+        let mut neighbour_tasks = vec![];
+
+        for (chunk_position, updates) in updates {
+            let mut chunk = chunks.remove(&chunk_position).unwrap_or_else(SparseChunk::new);
+            let rule = rule.clone();
+
+            neighbour_tasks.push(tasks.spawn(async move {
+                for offset in updates.spawns {
+                    MultiThreaded::update_neighbours_chunk(&mut chunk.cells, &rule, offset, true);
+                }
+
+                for offset in updates.deaths {
+                    MultiThreaded::update_neighbours_chunk(&mut chunk.cells, &rule, offset, false);
+                }
+
+                for (offset, increment) in updates.halo.into_iter().flatten() {
+                    if increment {
+                        chunk.cells.0[offset].neighbours += 1;
+                    } else {
+                        chunk.cells.0[offset].neighbours -= 1;
+                    }
+                }
+
+                (chunk_position, chunk)
+            }));
+        }
+
+        // collect chunks
+        for task in neighbour_tasks {
+            let (chunk_position, chunk) = future::block_on(task);
+
+            chunks.insert(chunk_position, chunk);
+        }
+
+        // free chunks that stayed empty
+        for chunk in chunks.values_mut() {
+            if chunk.is_empty() {
+                chunk.empty_steps += 1;
+            } else {
+                chunk.empty_steps = 0;
+            }
+        }
+
+        chunks.retain(|_, chunk| chunk.empty_steps < EMPTY_STEPS_BEFORE_FREE);
+
+        self.chunks = chunks;
+        self.generation += 1;
+    }
+
+    // set cells at xyz positions alive using given rule
+    pub fn seed(&mut self, rule: &Rule, positions: &[IVec3]) {
+        for position in positions {
+            let (chunk_position, offset) = split_position(*position);
+            let cell = &mut self.chunks.entry(chunk_position).or_insert_with(SparseChunk::new).cells.0[offset];
+
+            if cell.is_dead() {
+                cell.value = rule.states;
+                Self::update_neighbours(&mut self.chunks, rule, *position, true);
+
+                if let Some(changes) = &mut self.changes {
+                    changes.births.push(*position);
+                }
+            }
+        }
+    }
+
+    // kill every cell, freeing all chunks
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.generation = 0;
+
+        if let Some(changes) = &mut self.changes {
+            *changes = Changes::default();
+        }
+    }
+
+    pub fn record_changes(&mut self, enabled: bool) {
+        if enabled != self.changes.is_some() {
+            self.changes = enabled.then(Changes::default);
+        }
+    }
+
+    // take births + deaths collected since the last call
+    pub fn take_changes(&mut self) -> Changes {
+        self.changes.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    // replace every cell with the given values ; only cells in their first state count as neighbours
+    pub fn restore(&mut self, rule: &Rule, cells: &[(IVec3, u8)], generation: u64) {
+        self.clear();
+
+        for (position, value) in cells {
+            let (chunk_position, offset) = split_position(*position);
+            let cell = &mut self.chunks.entry(chunk_position).or_insert_with(SparseChunk::new).cells.0[offset];
+
+            if !cell.is_dead() {
+                continue;
+            }
+
+            cell.value = *value;
+
+            if *value == rule.states {
+                Self::update_neighbours(&mut self.chunks, rule, *position, true);
+            }
+        }
+
+        self.generation = generation;
+    }
+
+    // set the values of cells in place ; only cells entering or leaving their first state change neighbour counts
+    pub fn set_cells(&mut self, rule: &Rule, cells: &[(IVec3, u8)], generation: u64) {
+        for (position, value) in cells {
+            let (chunk_position, offset) = split_position(*position);
+
+            // a dead cell of a chunk that is not allocated is already dead
+            if *value == 0 && !self.chunks.contains_key(&chunk_position) {
+                continue;
+            }
+
+            let cell = &mut self.chunks.entry(chunk_position).or_insert_with(SparseChunk::new).cells.0[offset];
+            let previous = cell.value;
+
+            cell.value = *value;
+
+            if previous != rule.states && *value == rule.states {
+                Self::update_neighbours(&mut self.chunks, rule, *position, true);
+            } else if previous == rule.states && *value != rule.states {
+                Self::update_neighbours(&mut self.chunks, rule, *position, false);
+            }
+        }
+
+        self.generation = generation;
+    }
+}
+
+impl Simulation for Sparse {
+    fn step(&mut self, rule: &Rule) {
+        self.step(rule);
+    }
+
+    fn seed(&mut self, rule: &Rule, positions: &[IVec3]) {
+        self.seed(rule, positions);
+    }
+
+    fn clear(&mut self) {
+        self.clear();
+    }
+
+    fn restore(&mut self, rule: &Rule, cells: &[(IVec3, u8)], generation: u64) {
+        self.restore(rule, cells, generation);
+    }
+
+    fn set_cells(&mut self, rule: &Rule, cells: &[(IVec3, u8)], generation: u64) {
+        self.set_cells(rule, cells, generation);
+    }
+
+    fn record_changes(&mut self, enabled: bool) {
+        self.record_changes(enabled);
+    }
+
+    fn take_changes(&mut self) -> Changes {
+        self.take_changes()
+    }
+
+    fn cells(&self) -> Box<dyn Iterator<Item = (IVec3, Cell)> + '_> {
+        Box::new(self.cells())
+    }
+
+    fn cell_count(&self) -> usize {
+        self.cell_count()
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation()
+    }
+
+    fn set_bounds(&mut self, new_bounds: IVec3) -> IVec3 {
+        self.set_bounds(new_bounds)
+    }
+
+    fn bounds(&self) -> IVec3 {
+        self.bounds()
+    }
+
+    fn center(&self) -> IVec3 {
+        self.center()
+    }
+
+    // every cell of an unbounded world has no neighbours to begin with, a birth on 0 would fill it at once
+    fn check_rule(&self, rule: &Rule) -> Result<(), RuleError> {
+        if rule.birth_rule.in_range(0) {
+            return Err(RuleError::BirthWithoutNeighbours);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        boundary::{Boundaries, Boundary},
+        seeding::SeedRng
+    };
+
+    fn state(simulation: &dyn Simulation) -> Vec<(IVec3, Cell)> {
+        let center = simulation.center();
+        let mut cells: Vec<(IVec3, Cell)> = simulation.cells().map(|(position, cell)| (position - center, cell)).collect();
+
+        cells.sort_by_key(|(position, _)| position.to_array());
+        cells
+    }
+
+    #[test]
+    fn matches_bounded_world_with_dead_boundary() {
+        let rules = ["9-26/5-7,12-13,15/5/M", "0-6/1,3/2/N", "4/4/5/M"];
+        // large enough that nothing reaches the boundary, with the soup straddling chunk borders of the sparse world
+        let bounds = IVec3::splat(160);
+
+        for notation in rules {
+            let rule = Rule {
+                bounding_size: bounds,
+                boundary: Boundaries::all(Boundary::Dead),
+                ..notation.parse::<Rule>().unwrap()
+            };
+            let mut rng = SeedRng::seed_from_u64(13);
+            let offsets = (0..3000).map(|_| ivec3(rng.gen_range(-10..10), rng.gen_range(-10..10), rng.gen_range(-10..10))).collect::<Vec<_>>();
+            let mut bounded = MultiThreaded::new();
+            let mut sparse = Sparse::new();
+
+            bounded.set_bounds(bounds);
+            sparse.set_bounds(bounds);
+            bounded.seed(&rule, &offsets.iter().map(|offset| bounded.center() + *offset).collect::<Vec<_>>());
+            sparse.seed(&rule, &offsets);
+
+            for generation in 1..=30 {
+                bounded.step(&rule);
+                sparse.step(&rule);
+
+                let cells = state(&sparse);
+
+                assert_eq!(cells, state(&bounded), "generation {} of {} differs", generation, rule);
+                assert!(cells.iter().all(|(position, _)| position.abs().max_element() < bounds.x / 2 - 1), "{} reached the boundary", rule);
+            }
+
+            assert!(sparse.cell_count() > 0, "{} died out", rule);
+        }
+    }
+
+    #[test]
+    fn frees_chunks_after_staying_empty() {
+        // nothing survives and nothing is born, so the seeded cell dies on the first step
+        let rule = "/6/1/N".parse::<Rule>().unwrap();
+        let mut sparse = Sparse::new();
+
+        // a cell in the corner of a chunk reaches into the 3 chunks across its faces
+        sparse.seed(&rule, &[IVec3::splat(CHUNK_SIZE as i32 - 1)]);
+
+        assert_eq!(sparse.chunks.len(), 4);
+
+        sparse.step(&rule);
+
+        assert_eq!(sparse.cell_count(), 0);
+
+        for _ in 1..EMPTY_STEPS_BEFORE_FREE {
+            assert_eq!(sparse.chunks.len(), 4);
+            sparse.step(&rule);
+        }
+
+        assert!(sparse.chunks.is_empty());
+    }
+
+    #[test]
+    fn rejects_births_without_neighbours() {
+        let sparse = Sparse::new();
+
+        assert_eq!(sparse.check_rule(&"4/0,4/5/M".parse().unwrap()), Err(RuleError::BirthWithoutNeighbours));
+        assert_eq!(sparse.check_rule(&Rule::default()), Ok(()));
+        assert_eq!(MultiThreaded::new().check_rule(&"4/0,4/5/M".parse().unwrap()), Ok(()));
+    }
+}
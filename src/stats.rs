@@ -0,0 +1,218 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * March 22, 2023
+ */
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path
+};
+
+use crate::{
+    engine::{Changes, Simulation},
+    rule::Rule
+};
+
+// most neighbours a cell can have, in the Moore neighbourhood
+const MAX_NEIGHBOURS: usize = 26;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    // one row per generation, histograms spread over one column per bucket
+    Csv,
+    // array of one object per generation, histograms as arrays
+    Json
+}
+
+impl StatsFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            StatsFormat::Csv => "csv",
+            StatsFormat::Json => "json"
+        }
+    }
+}
+
+// population numbers of one generation
+#[derive(Clone, Default)]
+pub struct GenerationStats {
+    pub generation: u64,
+    pub live: usize,
+    pub births: usize,
+    pub deaths: usize,
+    // live cells in each state, indexed by value - 1 as cells of value 0 are dead and never counted
+    pub states: Vec<usize>,
+    // live cells with each neighbour count, indexed by count from 0 to 26
+    pub neighbours: Vec<usize>
+}
+
+impl GenerationStats {
+    // count the current generation of a simulation ; changes are the births + deaths that led to it
+    pub fn from_simulation(simulation: &dyn Simulation, rule: &Rule, changes: &Changes) -> GenerationStats {
+        let mut states = vec![0; rule.states as usize];
+        let mut neighbours = vec![0; MAX_NEIGHBOURS + 1];
+
+        for (_, cell) in simulation.cells() {
+            states[(cell.value as usize).clamp(1, rule.states as usize) - 1] += 1;
+            neighbours[(cell.neighbours as usize).min(MAX_NEIGHBOURS)] += 1;
+        }
+
+        GenerationStats {
+            generation: simulation.generation(),
+            live: simulation.cell_count(),
+            births: changes.births.len(),
+            deaths: changes.deaths.len(),
+            states,
+            neighbours
+        }
+    }
+}
+
+// time series of the population numbers of a run, dropping the oldest generation once it holds capacity generations
+pub struct Stats {
+    pub series: VecDeque<GenerationStats>,
+    capacity: usize
+}
+
+impl Stats {
+    pub fn new(capacity: usize) -> Stats {
+        Stats {
+            series: VecDeque::new(),
+            capacity
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.series.clear();
+    }
+
+    pub fn push(&mut self, stats: GenerationStats) {
+        self.series.push_back(stats);
+
+        while self.series.len() > self.capacity {
+            self.series.pop_front();
+        }
+    }
+
+    // count the current generation of a simulation and add it to the series
+    pub fn record(&mut self, simulation: &dyn Simulation, rule: &Rule, changes: &Changes) {
+        self.push(GenerationStats::from_simulation(simulation, rule, changes));
+    }
+
+    // forget every generation after generation, e.g. once the simulation was rewound
+    pub fn truncate(&mut self, generation: u64) {
+        self.series.retain(|stats| stats.generation <= generation);
+    }
+
+    pub fn last(&self) -> Option<&GenerationStats> {
+        self.series.back()
+    }
+
+    // get number of state columns, enough for every generation of the series
+    fn state_count(&self) -> usize {
+        self.series.iter().map(|stats| stats.states.len()).max().unwrap_or(0)
+    }
+
+    // write header and one row per generation, e.g. 'generation,live,births,deaths,state_1,...,neighbours_0,...'
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let state_count = self.state_count();
+
+        write!(writer, "generation,live,births,deaths")?;
+
+        for state in 1..=state_count {
+            write!(writer, ",state_{}", state)?;
+        }
+
+        for count in 0..=MAX_NEIGHBOURS {
+            write!(writer, ",neighbours_{}", count)?;
+        }
+
+        writeln!(writer)?;
+
+        for stats in &self.series {
+            write!(writer, "{},{},{},{}", stats.generation, stats.live, stats.births, stats.deaths)?;
+
+            for state in 0..state_count {
+                write!(writer, ",{}", stats.states.get(state).copied().unwrap_or(0))?;
+            }
+
+            for count in 0..=MAX_NEIGHBOURS {
+                write!(writer, ",{}", stats.neighbours.get(count).copied().unwrap_or(0))?;
+            }
+
+            writeln!(writer)?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let array = |values: &[usize]| values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(", ");
+
+        writeln!(writer, "[")?;
+
+        for (index, stats) in self.series.iter().enumerate() {
+            write!(writer, "  {{\"generation\": {}, \"live\": {}, \"births\": {}, \"deaths\": {}, \"states\": [{}], \"neighbours\": [{}]}}",
+                   stats.generation, stats.live, stats.births, stats.deaths, array(&stats.states), array(&stats.neighbours))?;
+            writeln!(writer, "{}", if index + 1 < self.series.len() { "," } else { "" })?;
+        }
+
+        writeln!(writer, "]")?;
+
+        writer.flush()
+    }
+
+    // save to path with the format's extension
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: StatsFormat) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path.as_ref().with_extension(format.extension()))?);
+
+        match format {
+            StatsFormat::Csv => self.write_csv(&mut writer),
+            StatsFormat::Json => self.write_json(&mut writer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(generation: u64) -> GenerationStats {
+        GenerationStats {
+            generation,
+            live: 3,
+            states: vec![1, 2],
+            neighbours: vec![0; MAX_NEIGHBOURS + 1],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn drops_oldest_generations_beyond_capacity() {
+        let mut series = Stats::new(4);
+
+        for generation in 0..10 {
+            series.push(stats(generation));
+        }
+
+        assert_eq!(series.series.iter().map(|stats| stats.generation).collect::<Vec<_>>(), [6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn state_columns_start_at_the_first_live_state() {
+        let mut series = Stats::new(4);
+        let mut csv = vec![];
+
+        series.push(stats(0));
+        series.write_csv(&mut csv).unwrap();
+
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+
+        assert!(lines.next().unwrap().starts_with("generation,live,births,deaths,state_1,state_2,neighbours_0,"));
+        assert!(lines.next().unwrap().starts_with("0,3,0,0,1,2,0,"));
+    }
+}
@@ -6,28 +6,25 @@
 
 use rand::Rng;
 
-use bevy::{
-    math::{ivec3, IVec3, Vec4},
-    prelude::Color
-};
+use bevy_math::{ivec3, IVec3, Vec4};
+
+use crate::colour::Colour;
 
 // wrap around coordinates outside of bounds
-pub fn wrap(position: IVec3, bounds: i32) -> IVec3 {
-    (position + bounds) % bounds
+pub fn wrap(position: IVec3, bounds: IVec3) -> IVec3 {
+    ivec3(position.x.rem_euclid(bounds.x), position.y.rem_euclid(bounds.y), position.z.rem_euclid(bounds.z))
 }
 
-// distance from cell to origin
-pub fn distance_to_center(cell_position: IVec3, bounds: i32) -> f32 {
+// distance from cell to origin, scaled so each axis reaches 1.0 at the bounds
+pub fn distance_to_center(cell_position: IVec3, bounds: IVec3) -> f32 {
     let cell_position = cell_position - center(bounds);
-    let max = bounds as f32 / 2.0;
+    let max = bounds.as_vec3() / 2.0;
 
-    cell_position.as_vec3().length() / max
+    (cell_position.as_vec3() / max).length()
 }
 
 // spawn cubes in within radius from origin
-pub fn spawn_noise<F: FnMut(IVec3)>(center: IVec3, radius: i32, amount: usize, mut f: F) {
-    let mut random = rand::thread_rng();
-
+pub fn spawn_noise<R: Rng, F: FnMut(IVec3)>(center: IVec3, radius: i32, amount: usize, random: &mut R, mut f: F) {
     (0..amount).for_each(|_| {
         f(center + ivec3(
             random.gen_range(-radius..=radius),
@@ -37,13 +34,8 @@ pub fn spawn_noise<F: FnMut(IVec3)>(center: IVec3, radius: i32, amount: usize, m
     });
 }
 
-// spawn noise wrapper with default values
-pub fn spawn_noise_default<F: FnMut(IVec3)>(center: IVec3, f: F) {
-    spawn_noise(center, 6, 12 * 12 * 12, f)
-}
-
 // linear interpolation between colour 1 and colour 2
-pub fn lerp_colour(colour_1: Color, colour_2: Color, dt: f32) -> Color {
+pub fn lerp_colour(colour_1: Colour, colour_2: Colour, dt: f32) -> Colour {
     let colour_1: Vec4 = colour_1.into();
     let colour_2: Vec4 = colour_2.into();
     let dt = dt.clamp(0.0, 1.0);
@@ -52,23 +44,22 @@ pub fn lerp_colour(colour_1: Color, colour_2: Color, dt: f32) -> Color {
 }
 
 // convert index to xyz position
-pub fn index_to_position(index: usize, bounds: i32) -> IVec3 {
-    ivec3(index as i32 % bounds, index as i32 / bounds % bounds, index as i32 / bounds / bounds)
+pub fn index_to_position(index: usize, bounds: IVec3) -> IVec3 {
+    let index = index as i32;
+
+    ivec3(index % bounds.x, index / bounds.x % bounds.y, index / bounds.x / bounds.y)
 }
 
 // convert xyz position to index
-pub fn position_to_index(position:IVec3, bounds: i32) -> usize {
+pub fn position_to_index(position: IVec3, bounds: IVec3) -> usize {
     let x = position.x as usize;
     let y = position.y as usize;
     let z = position.z as usize;
-    let bounds = bounds as usize;
 
-    x + y * bounds + z * bounds * bounds
+    x + y * bounds.x as usize + z * bounds.x as usize * bounds.y as usize
 }
 
 // get xyz position of center of bounds
-pub fn center(bounds: i32) -> IVec3 {
-    let center = bounds / 2;
-
-    ivec3(center, center, center)
+pub fn center(bounds: IVec3) -> IVec3 {
+    bounds / 2
 }
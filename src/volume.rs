@@ -0,0 +1,277 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * March 16, 2023
+ */
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf}
+};
+
+use bevy_math::IVec3;
+
+use crate::{
+    engine::Simulation,
+    utils
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum VolumeFormat {
+    // VTK ImageData, one cell per simulation cell
+    Vti,
+    // NumPy array of shape (z, y, x), neighbour counts go to a second file
+    Npy
+}
+
+impl VolumeFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            VolumeFormat::Vti => "vti",
+            VolumeFormat::Npy => "npy"
+        }
+    }
+}
+
+// state grid of one generation
+pub struct Volume {
+    pub size: IVec3,
+    // xyz position of the grid's minimum corner relative to the world's center
+    pub origin: IVec3,
+    pub generation: u64,
+    // x varying fastest, then y, then z
+    pub values: Vec<u8>,
+    pub neighbours: Option<Vec<u8>>
+}
+
+impl Volume {
+    // copy the state grid of a simulation, optionally with neighbour counts
+    pub fn from_simulation(simulation: &dyn Simulation, neighbours: bool) -> Volume {
+        let grid = simulation.grid();
+
+        Volume {
+            size: simulation.bounds(),
+            origin: -utils::center(simulation.bounds()),
+            generation: simulation.generation(),
+            values: grid.iter().map(|cell| cell.value).collect(),
+            neighbours: neighbours.then(|| grid.iter().map(|cell| cell.neighbours).collect())
+        }
+    }
+
+    // write VTK ImageData with the arrays appended as raw bytes
+    pub fn write_vti<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut arrays = vec![("value", &self.values)];
+
+        if let Some(neighbours) = &self.neighbours {
+            arrays.push(("neighbours", neighbours));
+        }
+
+        let extent = format!("0 {} 0 {} 0 {}", self.size.x, self.size.y, self.size.z);
+
+        writeln!(writer, "<?xml version=\"1.0\"?>")?;
+        writeln!(writer, "<VTKFile type=\"ImageData\" version=\"1.0\" byte_order=\"LittleEndian\" header_type=\"UInt32\">")?;
+        writeln!(writer, "  <ImageData WholeExtent=\"{}\" Origin=\"{} {} {}\" Spacing=\"1 1 1\">", extent,
+                 self.origin.x as f32 - 0.5, self.origin.y as f32 - 0.5, self.origin.z as f32 - 0.5)?;
+        writeln!(writer, "    <FieldData>")?;
+        writeln!(writer, "      <DataArray type=\"UInt64\" Name=\"generation\" NumberOfTuples=\"1\" format=\"ascii\">{}</DataArray>",
+                 self.generation)?;
+        writeln!(writer, "    </FieldData>")?;
+        writeln!(writer, "    <Piece Extent=\"{}\">", extent)?;
+        writeln!(writer, "      <CellData Scalars=\"value\">")?;
+
+        let mut offset = 0;
+
+        for (name, array) in &arrays {
+            writeln!(writer, "        <DataArray type=\"UInt8\" Name=\"{}\" format=\"appended\" offset=\"{}\"/>", name, offset)?;
+            offset += 4 + array.len();
+        }
+
+        writeln!(writer, "      </CellData>")?;
+        writeln!(writer, "    </Piece>")?;
+        writeln!(writer, "  </ImageData>")?;
+        write!(writer, "  <AppendedData encoding=\"raw\">\n   _")?;
+
+        for (_, array) in &arrays {
+            writer.write_all(&(array.len() as u32).to_le_bytes())?;
+            writer.write_all(array)?;
+        }
+
+        writeln!(writer, "\n  </AppendedData>")?;
+        writeln!(writer, "</VTKFile>")?;
+
+        writer.flush()
+    }
+
+    // write array of shape (z, y, x) as NumPy .npy version 1.0
+    pub fn write_npy<W: Write>(&self, writer: &mut W, array: &[u8]) -> io::Result<()> {
+        let mut header = format!("{{'descr': '|u1', 'fortran_order': False, 'shape': ({}, {}, {}), }}",
+                                 self.size.z, self.size.y, self.size.x);
+
+        // magic, version and header length take 10 bytes, the header is padded so the data starts 64 byte aligned
+        let padding = (64 - (10 + header.len() + 1) % 64) % 64;
+
+        header.push_str(&" ".repeat(padding));
+        header.push('\n');
+
+        writer.write_all(b"\x93NUMPY\x01\x00")?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+        writer.write_all(array)?;
+
+        writer.flush()
+    }
+
+    // save to path with the format's extension ; for .npy neighbour counts are saved next to it with a '_neighbours' suffix
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: VolumeFormat) -> io::Result<()> {
+        let path = path.as_ref();
+
+        match format {
+            VolumeFormat::Vti => self.write_vti(&mut BufWriter::new(File::create(path.with_extension(format.extension()))?)),
+            VolumeFormat::Npy => {
+                self.write_npy(&mut BufWriter::new(File::create(path.with_extension(format.extension()))?), &self.values)?;
+
+                if let Some(neighbours) = &self.neighbours {
+                    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+                    let path = path.with_file_name(format!("{}_neighbours.npy", stem));
+
+                    self.write_npy(&mut BufWriter::new(File::create(path)?), neighbours)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+// numbered series of volumes in a directory, e.g. 'volumes/run_000042.vti'
+pub struct VolumeSeries {
+    pub directory: PathBuf,
+    pub prefix: String,
+    pub formats: Vec<VolumeFormat>,
+    pub neighbours: bool
+}
+
+impl VolumeSeries {
+    // get path of generation, without extension
+    pub fn path(&self, generation: u64) -> PathBuf {
+        self.directory.join(format!("{}_{:06}", self.prefix, generation))
+    }
+
+    // save the current generation of a simulation in every format
+    pub fn write(&self, simulation: &dyn Simulation) -> io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+
+        let volume = Volume::from_simulation(simulation, self.neighbours);
+
+        for format in &self.formats {
+            volume.save(self.path(volume.generation), *format)?;
+        }
+
+        Ok(())
+    }
+
+    // step simulation with step until it reaches the first generation, then save every generation of the range ; step
+    // is given the simulation so whatever wraps a step (seeding, history, ...) happens as usual, and must advance it
+    // by one generation
+    pub fn write_range(&self, simulation: &mut dyn Simulation, generations: RangeInclusive<u64>,
+                       mut step: impl FnMut(&mut dyn Simulation)) -> io::Result<()> {
+        if simulation.generation() > *generations.start() {
+            return Err(io::Error::other(format!("generation {} has already passed", generations.start())));
+        }
+
+        let mut advance = |simulation: &mut dyn Simulation| {
+            let generation = simulation.generation();
+
+            step(simulation);
+
+            if simulation.generation() != generation + 1 {
+                return Err(io::Error::other(format!("step went from generation {} to {}", generation, simulation.generation())));
+            }
+
+            Ok(())
+        };
+
+        while simulation.generation() < *generations.start() {
+            advance(simulation)?;
+        }
+
+        loop {
+            self.write(simulation)?;
+
+            if simulation.generation() >= *generations.end() {
+                return Ok(());
+            }
+
+            advance(simulation)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        multi_threading::MultiThreaded,
+        rule::Rule
+    };
+
+    #[test]
+    fn write_range_steps_to_and_through_the_range() {
+        let rule = Rule {
+            bounding_size: IVec3::splat(8),
+            ..Default::default()
+        };
+        let series = VolumeSeries {
+            directory: std::env::temp_dir().join(format!("cellular_automata_volumes_{}", std::process::id())),
+            prefix: "generation".into(),
+            formats: vec![VolumeFormat::Npy],
+            neighbours: false
+        };
+        let mut simulation = MultiThreaded::new();
+        let mut steps = 0;
+
+        simulation.set_bounds(rule.bounding_size);
+        series.write_range(&mut simulation, 2..=4, |simulation| {
+            simulation.step(&rule);
+            steps += 1;
+        }).unwrap();
+
+        let written = (0..6).filter(|generation| series.path(*generation).with_extension("npy").exists()).collect::<Vec<_>>();
+
+        fs::remove_dir_all(&series.directory).unwrap();
+
+        assert_eq!(steps, 4);
+        assert_eq!(written, [2, 3, 4]);
+    }
+
+    #[test]
+    fn write_range_stops_when_a_step_does_not_advance() {
+        let series = VolumeSeries {
+            directory: std::env::temp_dir().join(format!("cellular_automata_stalled_{}", std::process::id())),
+            prefix: "generation".into(),
+            formats: vec![VolumeFormat::Npy],
+            neighbours: false
+        };
+        let mut simulation = MultiThreaded::new();
+        let mut steps = 0;
+
+        simulation.set_bounds(IVec3::splat(8));
+
+        let result = series.write_range(&mut simulation, 0..=3, |_| steps += 1);
+
+        assert!(result.is_err());
+        assert_eq!(steps, 1);
+        assert!(series.write_range(&mut simulation, 0..=0, |_| {}).is_ok());
+
+        fs::remove_dir_all(&series.directory).unwrap();
+
+        // a range that has already passed is refused before stepping
+        let rule = Rule::default();
+
+        simulation.step(&rule);
+
+        assert!(series.write_range(&mut simulation, 0..=3, |_| unreachable!()).is_err());
+    }
+}
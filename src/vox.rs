@@ -0,0 +1,517 @@
+/*
+ * Dylan Gilson
+ * dylan.gilson@outlook.com
+ * March 12, 2023
+ */
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs,
+    io,
+    path::Path
+};
+
+use bevy_math::{ivec3, IVec3};
+
+use crate::{
+    engine::Simulation,
+    rule::Rule
+};
+
+const VERSION: i32 = 150;
+
+// versions sharing the chunk layout read here ; MagicaVoxel writes 200 since 0.99.7
+const SUPPORTED_VERSIONS: [i32; 2] = [150, 200];
+
+// largest model MagicaVoxel accepts along each axis
+pub const MAX_MODEL_SIZE: i32 = 256;
+
+// palette index 0 is reserved for empty voxels
+const PALETTE_SIZE: usize = 255;
+
+// one model of a .vox file ; positions use the simulation's axes, y up
+#[derive(Clone)]
+pub struct VoxModel {
+    pub size: IVec3,
+    // offset of the model's minimum corner within the scene
+    pub offset: IVec3,
+    // xyz position within the model and palette index of every voxel
+    pub voxels: Vec<(IVec3, u8)>
+}
+
+#[derive(Clone)]
+pub struct Vox {
+    pub models: Vec<VoxModel>,
+    // RGBA colour of palette indices 1 to 255
+    pub palette: Vec<[u8; 4]>
+}
+
+#[derive(Debug)]
+pub enum VoxError {
+    Io(io::Error),
+    NotAVox,
+    UnsupportedVersion(i32),
+    Truncated,
+    MissingModel(usize)
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VoxError::Io(error) => write!(f, "{}", error),
+            VoxError::NotAVox => write!(f, "file is not a MagicaVoxel .vox file"),
+            VoxError::UnsupportedVersion(version) => write!(f, ".vox version {} is not supported", version),
+            VoxError::Truncated => write!(f, ".vox file is truncated"),
+            VoxError::MissingModel(index) => write!(f, ".vox file has no model {}", index)
+        }
+    }
+}
+
+impl std::error::Error for VoxError {}
+
+impl From<io::Error> for VoxError {
+    fn from(error: io::Error) -> Self {
+        VoxError::Io(error)
+    }
+}
+
+// MagicaVoxel is z up, the simulation is y up
+fn swap_up(position: IVec3) -> IVec3 {
+    ivec3(position.x, position.z, position.y)
+}
+
+// reduce colours to at most 255 palette entries by dropping low bits until they fit, averaging each bucket ;
+// with 7 bits dropped every channel has 2 values left, so at most 16 buckets remain
+fn quantise(colours: &[[u8; 4]]) -> (Vec<[u8; 4]>, Vec<u8>) {
+    for shift in 0..8 {
+        let key = |colour: &[u8; 4]| colour.map(|channel| channel >> shift);
+        let mut buckets: HashMap<[u8; 4], usize> = HashMap::new();
+
+        for colour in colours {
+            let next = buckets.len();
+
+            buckets.entry(key(colour)).or_insert(next);
+        }
+
+        if buckets.len() > PALETTE_SIZE && shift < 7 {
+            continue;
+        }
+
+        let mut sums = vec![[0u32; 4]; buckets.len()];
+        let mut counts = vec![0u32; buckets.len()];
+        let indices = colours.iter().map(|colour| {
+            let bucket = buckets[&key(colour)];
+
+            for (sum, channel) in sums[bucket].iter_mut().zip(colour) {
+                *sum += *channel as u32;
+            }
+
+            counts[bucket] += 1;
+
+            (bucket + 1) as u8
+        }).collect();
+        let palette = sums.iter().zip(counts).map(|(sum, count)| sum.map(|channel| (channel / count.max(1)) as u8)).collect();
+
+        return (palette, indices);
+    }
+
+    unreachable!()
+}
+
+impl Vox {
+    // build .vox scene from the live cells of a simulation, coloured by the rule's colour method
+    pub fn from_simulation(simulation: &dyn Simulation, rule: &Rule) -> Vox {
+        let cells = simulation.cells().collect::<Vec<_>>();
+
+        if cells.is_empty() {
+            return Vox {
+                models: vec![],
+                palette: vec![]
+            };
+        }
+
+        let colours = cells.iter()
+            .map(|(position, cell)| simulation.colour(rule, *position, *cell).as_rgba_u8())
+            .collect::<Vec<_>>();
+        let (palette, indices) = quantise(&colours);
+        let min = cells.iter().fold(IVec3::splat(i32::MAX), |min, (position, _)| min.min(*position));
+        let max_model_size = IVec3::splat(MAX_MODEL_SIZE);
+
+        // split into models of at most 256 per side
+        let mut models: HashMap<IVec3, Vec<(IVec3, u8)>> = HashMap::new();
+
+        for ((position, _), index) in cells.iter().zip(indices) {
+            let position = *position - min;
+            let model = ivec3(position.x.div_euclid(MAX_MODEL_SIZE), position.y.div_euclid(MAX_MODEL_SIZE), position.z.div_euclid(MAX_MODEL_SIZE));
+
+            models.entry(model).or_default().push((position - model * max_model_size, index));
+        }
+
+        let mut models = models.into_iter().map(|(model, voxels)| {
+            let size = voxels.iter().fold(IVec3::ZERO, |size, (position, _)| size.max(*position + IVec3::ONE));
+
+            VoxModel {
+                size,
+                offset: model * max_model_size,
+                voxels
+            }
+        }).collect::<Vec<_>>();
+
+        models.sort_by_key(|model| model.offset.to_array());
+
+        Vox {
+            models,
+            palette
+        }
+    }
+
+    // set cells of a model alive with its minimum corner at xyz position
+    pub fn seed(&self, simulation: &mut dyn Simulation, rule: &Rule, model: usize, position: IVec3) -> Result<(), VoxError> {
+        let model = self.models.get(model).ok_or(VoxError::MissingModel(model))?;
+        let positions = model.voxels.iter().map(|(voxel, _)| position + *voxel).collect::<Vec<_>>();
+
+        simulation.seed(rule, &positions);
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut children = vec![];
+
+        for model in &self.models {
+            let mut size = vec![];
+
+            for axis in swap_up(model.size).to_array() {
+                size.extend(axis.to_le_bytes());
+            }
+
+            write_chunk(&mut children, b"SIZE", &size);
+
+            let mut xyzi = (model.voxels.len() as u32).to_le_bytes().to_vec();
+
+            for (position, index) in &model.voxels {
+                let position = swap_up(*position);
+
+                xyzi.extend([position.x as u8, position.y as u8, position.z as u8, *index]);
+            }
+
+            write_chunk(&mut children, b"XYZI", &xyzi);
+        }
+
+        // scene graph placing every model : root transform -> group -> transform + shape per model
+        if self.models.len() > 1 {
+            let mut node = vec![];
+
+            write_transform(&mut node, 0, 1, IVec3::ZERO);
+            write_chunk(&mut children, b"nTRN", &node);
+
+            let mut group = vec![];
+
+            group.extend(1i32.to_le_bytes());
+            write_dict(&mut group, &[]);
+            group.extend((self.models.len() as i32).to_le_bytes());
+
+            for index in 0..self.models.len() {
+                group.extend((2 + 2 * index as i32).to_le_bytes());
+            }
+
+            write_chunk(&mut children, b"nGRP", &group);
+
+            for (index, model) in self.models.iter().enumerate() {
+                let id = 2 + 2 * index as i32;
+                let mut transform = vec![];
+                let mut shape = vec![];
+
+                // translations point at the center of a model
+                write_transform(&mut transform, id, id + 1, swap_up(model.offset + model.size / 2));
+                write_chunk(&mut children, b"nTRN", &transform);
+
+                shape.extend((id + 1).to_le_bytes());
+                write_dict(&mut shape, &[]);
+                shape.extend(1i32.to_le_bytes());
+                shape.extend((index as i32).to_le_bytes());
+                write_dict(&mut shape, &[]);
+                write_chunk(&mut children, b"nSHP", &shape);
+            }
+        }
+
+        let mut palette = vec![0u8; 256 * 4];
+
+        for (index, colour) in self.palette.iter().enumerate().take(PALETTE_SIZE) {
+            palette[index * 4..index * 4 + 4].copy_from_slice(colour);
+        }
+
+        write_chunk(&mut children, b"RGBA", &palette);
+
+        let mut bytes = b"VOX ".to_vec();
+
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.extend(b"MAIN");
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend((children.len() as u32).to_le_bytes());
+        bytes.extend(children);
+
+        bytes
+    }
+
+    // read models and palette ; scene graph translations are used to place models when present
+    pub fn from_bytes(bytes: &[u8]) -> Result<Vox, VoxError> {
+        let mut reader = bytes;
+
+        if take(&mut reader, 4)? != b"VOX " {
+            return Err(VoxError::NotAVox);
+        }
+
+        let version = read_i32(&mut reader)?;
+
+        if !SUPPORTED_VERSIONS.contains(&version) {
+            return Err(VoxError::UnsupportedVersion(version));
+        }
+
+        let mut models = vec![];
+        let mut palette = vec![];
+        let mut translations: HashMap<i32, IVec3> = HashMap::new();
+        let mut shapes: HashMap<i32, i32> = HashMap::new();
+        let mut size = IVec3::ZERO;
+
+        // MAIN holds every other chunk as a child, so its header is skipped and chunks are read in order
+        while !reader.is_empty() {
+            let id = take(&mut reader, 4)?;
+            let content_size = read_u32(&mut reader)? as usize;
+            let children_size = read_u32(&mut reader)? as usize;
+
+            // a file cut between two chunks would otherwise read as a smaller scene
+            if id == b"MAIN" {
+                if children_size != reader.len() {
+                    return Err(VoxError::Truncated);
+                }
+
+                continue;
+            }
+
+            let mut content = take(&mut reader, content_size)?;
+
+            match id {
+                b"SIZE" => size = swap_up(ivec3(read_i32(&mut content)?, read_i32(&mut content)?, read_i32(&mut content)?)),
+                b"XYZI" => {
+                    let count = read_u32(&mut content)? as usize;
+                    let mut voxels = Vec::with_capacity(count.min(content.len() / 4));
+
+                    for _ in 0..count {
+                        let voxel = take(&mut content, 4)?;
+
+                        voxels.push((swap_up(ivec3(voxel[0] as i32, voxel[1] as i32, voxel[2] as i32)), voxel[3]));
+                    }
+
+                    models.push(VoxModel {
+                        size,
+                        offset: IVec3::ZERO,
+                        voxels
+                    });
+                },
+                b"RGBA" => {
+                    palette = content.chunks_exact(4).take(PALETTE_SIZE).map(|colour| [colour[0], colour[1], colour[2], colour[3]]).collect();
+                },
+                b"nTRN" => {
+                    // node id and attributes
+                    read_i32(&mut content)?;
+                    skip_dict(&mut content)?;
+
+                    // child node id, then reserved id and layer id
+                    let child = read_i32(&mut content)?;
+
+                    take(&mut content, 8)?;
+
+                    if read_i32(&mut content)? > 0 {
+                        let frame = read_dict(&mut content)?;
+
+                        if let Some(translation) = frame.get("_t") {
+                            let axes = translation.split_whitespace().filter_map(|axis| axis.parse::<i32>().ok()).collect::<Vec<_>>();
+
+                            if let [x, y, z] = axes[..] {
+                                translations.insert(child, swap_up(ivec3(x, y, z)));
+                            }
+                        }
+                    }
+                },
+                b"nSHP" => {
+                    let node = read_i32(&mut content)?;
+
+                    skip_dict(&mut content)?;
+
+                    if read_i32(&mut content)? > 0 {
+                        shapes.insert(read_i32(&mut content)?, node);
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        for (index, model) in models.iter_mut().enumerate() {
+            if let Some(translation) = shapes.get(&(index as i32)).and_then(|node| translations.get(node)) {
+                model.offset = *translation - model.size / 2;
+            }
+        }
+
+        Ok(Vox {
+            models,
+            palette
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), VoxError> {
+        Ok(fs::write(path, self.to_bytes())?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Vox, VoxError> {
+        Vox::from_bytes(&fs::read(path)?)
+    }
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8]) {
+    bytes.extend(id);
+    bytes.extend((content.len() as u32).to_le_bytes());
+    bytes.extend(0u32.to_le_bytes());
+    bytes.extend(content);
+}
+
+fn write_string(bytes: &mut Vec<u8>, string: &str) {
+    bytes.extend((string.len() as i32).to_le_bytes());
+    bytes.extend(string.as_bytes());
+}
+
+fn write_dict(bytes: &mut Vec<u8>, entries: &[(&str, String)]) {
+    bytes.extend((entries.len() as i32).to_le_bytes());
+
+    for (key, value) in entries {
+        write_string(bytes, key);
+        write_string(bytes, value);
+    }
+}
+
+// transform node with a single frame translating its child
+fn write_transform(bytes: &mut Vec<u8>, node: i32, child: i32, translation: IVec3) {
+    bytes.extend(node.to_le_bytes());
+    write_dict(bytes, &[]);
+    bytes.extend(child.to_le_bytes());
+    bytes.extend((-1i32).to_le_bytes());
+    bytes.extend((-1i32).to_le_bytes());
+    bytes.extend(1i32.to_le_bytes());
+    write_dict(bytes, &[("_t", format!("{} {} {}", translation.x, translation.y, translation.z))]);
+}
+
+fn take<'a>(bytes: &mut &'a [u8], count: usize) -> Result<&'a [u8], VoxError> {
+    if count > bytes.len() {
+        return Err(VoxError::Truncated);
+    }
+
+    let (taken, rest) = bytes.split_at(count);
+
+    *bytes = rest;
+
+    Ok(taken)
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Result<u32, VoxError> {
+    Ok(u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()))
+}
+
+fn read_i32(bytes: &mut &[u8]) -> Result<i32, VoxError> {
+    Ok(i32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()))
+}
+
+fn read_string(bytes: &mut &[u8]) -> Result<String, VoxError> {
+    let length = read_u32(bytes)? as usize;
+
+    Ok(String::from_utf8_lossy(take(bytes, length)?).into_owned())
+}
+
+fn read_dict(bytes: &mut &[u8]) -> Result<HashMap<String, String>, VoxError> {
+    let count = read_u32(bytes)?;
+    let mut dict = HashMap::new();
+
+    for _ in 0..count {
+        let key = read_string(bytes)?;
+
+        dict.insert(key, read_string(bytes)?);
+    }
+
+    Ok(dict)
+}
+
+fn skip_dict(bytes: &mut &[u8]) -> Result<(), VoxError> {
+    read_dict(bytes).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vox() -> Vox {
+        Vox {
+            models: vec![
+                VoxModel {
+                    size: ivec3(4, 3, 2),
+                    offset: IVec3::ZERO,
+                    voxels: vec![(ivec3(0, 0, 0), 1), (ivec3(3, 2, 1), 2), (ivec3(1, 2, 0), 1)]
+                },
+                VoxModel {
+                    size: ivec3(6, 2, 8),
+                    offset: ivec3(MAX_MODEL_SIZE, 0, 0),
+                    voxels: vec![(ivec3(5, 1, 7), 2)]
+                }
+            ],
+            palette: vec![[255, 0, 0, 255], [0, 128, 255, 255]]
+        }
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let expected = vox();
+        let read = Vox::from_bytes(&expected.to_bytes()).unwrap();
+
+        assert_eq!(read.models.len(), expected.models.len());
+
+        for (read, expected) in read.models.iter().zip(&expected.models) {
+            assert_eq!(read.size, expected.size);
+            assert_eq!(read.offset, expected.offset);
+            assert_eq!(read.voxels, expected.voxels);
+        }
+
+        // the palette chunk always holds 255 entries, unused ones are black
+        assert_eq!(read.palette[..expected.palette.len()], expected.palette[..]);
+        assert!(read.palette[expected.palette.len()..].iter().all(|colour| *colour == [0; 4]));
+    }
+
+    #[test]
+    fn rejects_truncated_file() {
+        let bytes = vox().to_bytes();
+
+        for length in [0, 6, 12, 20, bytes.len() / 2, bytes.len() - 1024, bytes.len() - 1] {
+            assert!(matches!(Vox::from_bytes(&bytes[..length]), Err(VoxError::Truncated)), "length {}", length);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = vox().to_bytes();
+
+        bytes[..4].copy_from_slice(b"PNG ");
+
+        assert!(matches!(Vox::from_bytes(&bytes), Err(VoxError::NotAVox)));
+    }
+
+    #[test]
+    fn rejects_other_version() {
+        let mut bytes = vox().to_bytes();
+
+        bytes[4..8].copy_from_slice(&300i32.to_le_bytes());
+
+        assert!(matches!(Vox::from_bytes(&bytes), Err(VoxError::UnsupportedVersion(300))));
+
+        bytes[4..8].copy_from_slice(&200i32.to_le_bytes());
+
+        assert!(Vox::from_bytes(&bytes).is_ok());
+    }
+}